@@ -0,0 +1,567 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::disasm::ROM_START;
+
+// How deep includes and constants referring to constants may nest.
+const MAX_DEPTH: usize = 16;
+
+// Names that mean an operand, not a symbol.
+const RESERVED: [&str; 26] = [
+    "v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7", "v8", "v9", "va", "vb", "vc", "vd", "ve", "vf",
+    "i", "dt", "st", "k", "f", "hf", "b", "r", "long", "db",
+];
+
+/// Why a source couldn't be assembled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    /// The file the error is in, `<input>` for source given to [`assemble`].
+    pub file: String,
+    /// Line in the file, counting from 1.
+    pub line: usize,
+    /// Column in the line, counting from 1.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+impl error::Error for AsmError {}
+
+/// Assembles CHIP-8 source into a ROM to be loaded at 0x200.
+///
+/// The syntax is the one the disassembler prints: one instruction per line
+/// (`LD V3, 0x10`, `DRW V0, V1, 5`, `LD I, LONG sprite`), `label:` before
+/// any line, `NAME = value` constants, `db`/`dw` for bytes and big-endian
+/// words, `include "file.asm"` and `;` comments. Values are numbers
+/// (`42`, `0x2a`, `0b101010`), labels and constants, added or subtracted
+/// (`sprites + 5`). Mnemonics and registers are case-insensitive, symbols
+/// aren't. Includes are looked up relative to the current directory.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut asm = Assembler::new();
+    asm.source(source, "<input>", Path::new("."), 0)?;
+    asm.finish()
+}
+
+/// Like [`assemble`], reading the source from `path`. Includes are looked up
+/// relative to the including file.
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, AsmError> {
+    let path = path.as_ref();
+    let name = path.display().to_string();
+    let source = fs::read_to_string(path).map_err(|e| AsmError {
+        file: name.clone(),
+        line: 0,
+        column: 0,
+        message: e.to_string(),
+    })?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut asm = Assembler::new();
+    asm.source(&source, &name, dir, 0)?;
+    asm.finish()
+}
+
+// A place in the source: index into `Assembler::files`, line and column.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Loc {
+    file: usize,
+    line: usize,
+    column: usize,
+}
+
+// An operand or value as written, to be evaluated once all labels are known.
+#[derive(Clone, Debug)]
+struct Arg {
+    text: String,
+    loc: Loc,
+}
+
+enum Item {
+    Instruction { mnemonic: String, args: Vec<Arg> },
+    Bytes(Vec<Arg>),
+    Words(Vec<Arg>),
+}
+
+struct Statement {
+    loc: Loc,
+    item: Item,
+}
+
+enum Symbol {
+    Label(u16),
+    Constant(Arg),
+}
+
+// An instruction operand.
+enum Operand<'a> {
+    V(u16),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Long(Arg),
+    Value(&'a Arg),
+}
+
+struct Assembler {
+    files: Vec<String>,
+    statements: Vec<Statement>,
+    symbols: HashMap<String, (Symbol, Loc)>,
+    // Address of the next statement, kept wide to catch overflow
+    addr: u32,
+}
+
+impl Assembler {
+    fn new() -> Assembler {
+        Assembler {
+            files: Vec::new(),
+            statements: Vec::new(),
+            symbols: HashMap::new(),
+            addr: ROM_START as u32,
+        }
+    }
+
+    fn error(&self, loc: Loc, message: String) -> AsmError {
+        AsmError {
+            file: self.files[loc.file].clone(),
+            line: loc.line,
+            column: loc.column,
+            message,
+        }
+    }
+
+    // First pass: splits a file into statements, laying them out in memory
+    // and recording where every label lands.
+    fn source(&mut self, text: &str, name: &str, dir: &Path, depth: usize) -> Result<(), AsmError> {
+        let file = self.files.len();
+        self.files.push(name.to_string());
+        for (n, line) in text.lines().enumerate() {
+            self.line(strip_comment(line), file, n + 1, dir, depth)?;
+        }
+        Ok(())
+    }
+
+    fn line(
+        &mut self,
+        text: &str,
+        file: usize,
+        line: usize,
+        dir: &Path,
+        depth: usize,
+    ) -> Result<(), AsmError> {
+        let at = |pos: usize| Loc {
+            file,
+            line,
+            column: pos + 1,
+        };
+        let mut pos = skip_space(text, 0);
+
+        // Any number of `label:` in front
+        loop {
+            let end = ident_end(text, pos);
+            if end == pos || !text[end..].starts_with(':') {
+                break;
+            }
+            // A label after the last byte of memory has nowhere to point
+            let addr = u16::try_from(self.addr)
+                .map_err(|_| self.error(at(pos), "the label doesn't fit in memory".to_string()))?;
+            self.define(&text[pos..end], Symbol::Label(addr), at(pos))?;
+            pos = skip_space(text, end + 1);
+        }
+        if pos == text.len() {
+            return Ok(());
+        }
+
+        let word_end = text[pos..]
+            .find(char::is_whitespace)
+            .map_or(text.len(), |w| pos + w);
+        let word = &text[pos..word_end];
+        let rest = skip_space(text, word_end);
+
+        // NAME = value, other lines with a `=` are left to fail as instructions
+        let name_end = ident_end(text, pos);
+        let eq = skip_space(text, name_end);
+        if name_end > pos && text[eq..].starts_with('=') {
+            let name = &text[pos..name_end];
+            let value = skip_space(text, eq + 1);
+            if value == text.len() {
+                return Err(self.error(at(value), format!("{} needs a value", name)));
+            }
+            let arg = Arg {
+                text: text[value..].trim_end().to_string(),
+                loc: at(value),
+            };
+            return self.define(name, Symbol::Constant(arg), at(pos));
+        }
+
+        let (item, size) = match word.to_ascii_lowercase().as_str() {
+            "include" => {
+                let path = text[rest..].trim_end();
+                if path.len() < 2 || !path.starts_with('"') || !path.ends_with('"') {
+                    return Err(self.error(at(rest), "include needs a \"file\"".to_string()));
+                }
+                if depth >= MAX_DEPTH {
+                    return Err(self.error(at(pos), "includes nest too deep".to_string()));
+                }
+                let path = dir.join(&path[1..path.len() - 1]);
+                let source = fs::read_to_string(&path).map_err(|e| {
+                    self.error(at(rest), format!("can't include {}: {}", path.display(), e))
+                })?;
+                let inner = path.parent().unwrap_or(dir).to_path_buf();
+                return self.source(&source, &path.display().to_string(), &inner, depth + 1);
+            }
+            "db" => {
+                let args = self.args(text, rest, at)?;
+                let size = args.len() as u32;
+                (Item::Bytes(args), size)
+            }
+            "dw" => {
+                let args = self.args(text, rest, at)?;
+                let size = 2 * args.len() as u32;
+                (Item::Words(args), size)
+            }
+            _ => {
+                let args = if rest == text.len() {
+                    Vec::new()
+                } else {
+                    self.args(text, rest, at)?
+                };
+                let long = args
+                    .iter()
+                    .any(|a| a.text.to_ascii_lowercase().starts_with("long "));
+                let mnemonic = word.to_ascii_uppercase();
+                (
+                    Item::Instruction { mnemonic, args },
+                    if long { 4 } else { 2 },
+                )
+            }
+        };
+        if item_is_empty(&item) {
+            return Err(self.error(at(pos), format!("{} needs at least one value", word)));
+        }
+        self.statements.push(Statement { loc: at(pos), item });
+        self.addr += size;
+        if self.addr > 0x10000 {
+            return Err(self.error(at(pos), "the program doesn't fit in memory".to_string()));
+        }
+        Ok(())
+    }
+
+    // Comma separated operands starting at `pos`.
+    fn args(
+        &self,
+        text: &str,
+        pos: usize,
+        at: impl Fn(usize) -> Loc,
+    ) -> Result<Vec<Arg>, AsmError> {
+        let mut args = Vec::new();
+        let mut start = pos;
+        for piece in text[pos..].split(',') {
+            let lead = piece.len() - piece.trim_start().len();
+            let value = piece.trim();
+            if value.is_empty() {
+                return Err(self.error(at(start + lead), "missing operand".to_string()));
+            }
+            args.push(Arg {
+                text: value.to_string(),
+                loc: at(start + lead),
+            });
+            start += piece.len() + 1;
+        }
+        Ok(args)
+    }
+
+    fn define(&mut self, name: &str, symbol: Symbol, loc: Loc) -> Result<(), AsmError> {
+        if RESERVED.contains(&name.to_ascii_lowercase().as_str()) {
+            return Err(self.error(loc, format!("{} is a reserved name", name)));
+        }
+        if let Some((_, prev)) = self.symbols.get(name) {
+            let message = format!(
+                "{} is already defined at {}:{}",
+                name, self.files[prev.file], prev.line
+            );
+            return Err(self.error(loc, message));
+        }
+        self.symbols.insert(name.to_string(), (symbol, loc));
+        Ok(())
+    }
+
+    // Second pass: encodes every statement now that all symbols are known.
+    fn finish(&self) -> Result<Vec<u8>, AsmError> {
+        let mut out = Vec::with_capacity(self.addr as usize - ROM_START as usize);
+        for s in self.statements.iter() {
+            match &s.item {
+                Item::Bytes(args) => {
+                    for a in args {
+                        out.push(self.byte(a)?);
+                    }
+                }
+                Item::Words(args) => {
+                    for a in args {
+                        let v = self.ranged(a, -0x8000, 0xFFFF)? as u16;
+                        out.extend_from_slice(&v.to_be_bytes());
+                    }
+                }
+                Item::Instruction { mnemonic, args } => {
+                    for word in self.encode(s.loc, mnemonic, args)? {
+                        out.extend_from_slice(&word.to_be_bytes());
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn encode(&self, loc: Loc, mnemonic: &str, args: &[Arg]) -> Result<Vec<u16>, AsmError> {
+        use Operand::*;
+
+        let ops: Vec<Operand> = args.iter().map(operand).collect();
+        let xy = |x: u16, y: u16| x << 8 | y << 4;
+        let word = match (mnemonic, ops.as_slice()) {
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("SCD", [Value(n)]) => 0x00C0 | self.nibble(n)?,
+            ("SCU", [Value(n)]) => 0x00D0 | self.nibble(n)?,
+            ("SCR", []) => 0x00FB,
+            ("SCL", []) => 0x00FC,
+            ("EXIT", []) => 0x00FD,
+            ("LOW", []) => 0x00FE,
+            ("HIGH", []) => 0x00FF,
+            ("JP", [Value(a)]) => 0x1000 | self.address(a)?,
+            ("JP", [V(0), Value(a)]) => 0xB000 | self.address(a)?,
+            ("CALL", [Value(a)]) => 0x2000 | self.address(a)?,
+            ("SE", [V(x), Value(nn)]) => 0x3000 | x << 8 | self.byte(nn)? as u16,
+            ("SNE", [V(x), Value(nn)]) => 0x4000 | x << 8 | self.byte(nn)? as u16,
+            ("SE", [V(x), V(y)]) => 0x5000 | xy(*x, *y),
+            ("SAVE", [V(x), V(y)]) => 0x5002 | xy(*x, *y),
+            ("LOAD", [V(x), V(y)]) => 0x5003 | xy(*x, *y),
+            ("LD", [V(x), Value(nn)]) => 0x6000 | x << 8 | self.byte(nn)? as u16,
+            ("ADD", [V(x), Value(nn)]) => 0x7000 | x << 8 | self.byte(nn)? as u16,
+            ("LD", [V(x), V(y)]) => 0x8000 | xy(*x, *y),
+            ("OR", [V(x), V(y)]) => 0x8001 | xy(*x, *y),
+            ("AND", [V(x), V(y)]) => 0x8002 | xy(*x, *y),
+            ("XOR", [V(x), V(y)]) => 0x8003 | xy(*x, *y),
+            ("ADD", [V(x), V(y)]) => 0x8004 | xy(*x, *y),
+            ("SUB", [V(x), V(y)]) => 0x8005 | xy(*x, *y),
+            ("SHR", [V(x), V(y)]) => 0x8006 | xy(*x, *y),
+            ("SHR", [V(x)]) => 0x8006 | xy(*x, *x),
+            ("SUBN", [V(x), V(y)]) => 0x8007 | xy(*x, *y),
+            ("SHL", [V(x), V(y)]) => 0x800E | xy(*x, *y),
+            ("SHL", [V(x)]) => 0x800E | xy(*x, *x),
+            ("SNE", [V(x), V(y)]) => 0x9000 | xy(*x, *y),
+            ("LD", [I, Value(a)]) => 0xA000 | self.address(a)?,
+            ("LD", [I, Long(a)]) => {
+                let nnnn = self.ranged(a, 0, 0xFFFF)? as u16;
+                return Ok(vec![0xF000, nnnn]);
+            }
+            ("RND", [V(x), Value(nn)]) => 0xC000 | x << 8 | self.byte(nn)? as u16,
+            ("DRW", [V(x), V(y), Value(n)]) => 0xD000 | xy(*x, *y) | self.nibble(n)?,
+            ("SKP", [V(x)]) => 0xE09E | x << 8,
+            ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+            ("PLANE", [Value(n)]) => 0xF001 | self.nibble(n)? << 8,
+            ("AUDIO", []) => 0xF002,
+            ("LD", [V(x), Dt]) => 0xF007 | x << 8,
+            ("LD", [V(x), K]) => 0xF00A | x << 8,
+            ("LD", [Dt, V(x)]) => 0xF015 | x << 8,
+            ("LD", [St, V(x)]) => 0xF018 | x << 8,
+            ("ADD", [I, V(x)]) => 0xF01E | x << 8,
+            ("LD", [F, V(x)]) => 0xF029 | x << 8,
+            ("LD", [Hf, V(x)]) => 0xF030 | x << 8,
+            ("PITCH", [V(x)]) => 0xF03A | x << 8,
+            ("LD", [B, V(x)]) => 0xF033 | x << 8,
+            ("LD", [IndirectI, V(x)]) => 0xF055 | x << 8,
+            ("LD", [V(x), IndirectI]) => 0xF065 | x << 8,
+            ("LD", [R, V(x)]) => 0xF075 | x << 8,
+            ("LD", [V(x), R]) => 0xF085 | x << 8,
+            _ => {
+                const KNOWN: [&str; 31] = [
+                    "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL",
+                    "SE", "SNE", "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR",
+                    "SUBN", "SHL", "RND", "DRW", "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+                ];
+                let message = if KNOWN.contains(&mnemonic) {
+                    format!("{} doesn't take these operands", mnemonic)
+                } else {
+                    format!("unknown instruction {}", mnemonic)
+                };
+                return Err(self.error(loc, message));
+            }
+        };
+        Ok(vec![word])
+    }
+
+    fn address(&self, arg: &Arg) -> Result<u16, AsmError> {
+        Ok(self.ranged(arg, 0, 0xFFF)? as u16)
+    }
+
+    // Negative bytes are stored as two's complement, so `ADD V0, -1` works.
+    fn byte(&self, arg: &Arg) -> Result<u8, AsmError> {
+        Ok(self.ranged(arg, -0x80, 0xFF)? as u8)
+    }
+
+    fn nibble(&self, arg: &Arg) -> Result<u16, AsmError> {
+        Ok(self.ranged(arg, 0, 0xF)? as u16)
+    }
+
+    fn ranged(&self, arg: &Arg, min: i64, max: i64) -> Result<i64, AsmError> {
+        let v = self.eval(arg, 0)?;
+        if v < min || v > max {
+            let message = format!("{} is out of range ({} to {:#x})", v, min, max);
+            return Err(self.error(arg.loc, message));
+        }
+        Ok(v)
+    }
+
+    // Evaluates `term (+|- term)*`, terms being numbers or symbols.
+    fn eval(&self, arg: &Arg, depth: usize) -> Result<i64, AsmError> {
+        let text = arg.text.as_str();
+        let at = |pos: usize| Loc {
+            column: arg.loc.column + pos,
+            ..arg.loc
+        };
+        let mut total: i64 = 0;
+        let mut sign: i64 = 1;
+        let mut pos = skip_space(text, 0);
+        loop {
+            // Unary signs
+            while let Some(c) = text[pos..].chars().next().filter(|&c| c == '-' || c == '+') {
+                if c == '-' {
+                    sign = -sign;
+                }
+                pos = skip_space(text, pos + 1);
+            }
+            let end = ident_end(text, pos);
+            if end == pos {
+                return Err(self.error(at(pos), "expected a value".to_string()));
+            }
+            let term = self.term(&text[pos..end], at(pos), depth)?;
+            total = sign
+                .checked_mul(term)
+                .and_then(|t| total.checked_add(t))
+                .ok_or_else(|| self.error(arg.loc, format!("{} overflows", text.trim())))?;
+            pos = skip_space(text, end);
+            sign = match text[pos..].chars().next() {
+                None => return Ok(total),
+                Some('+') => 1,
+                Some('-') => -1,
+                Some(c) => return Err(self.error(at(pos), format!("unexpected {:?}", c))),
+            };
+            pos = skip_space(text, pos + 1);
+        }
+    }
+
+    fn term(&self, term: &str, loc: Loc, depth: usize) -> Result<i64, AsmError> {
+        if term.starts_with(|c: char| c.is_ascii_digit()) {
+            return parse_number(term)
+                .ok_or_else(|| self.error(loc, format!("{:?} isn't a number", term)));
+        }
+        match self.symbols.get(term) {
+            Some((Symbol::Label(addr), _)) => Ok(*addr as i64),
+            Some((Symbol::Constant(value), _)) => {
+                if depth >= MAX_DEPTH {
+                    return Err(self.error(loc, format!("{} is defined in terms of itself", term)));
+                }
+                self.eval(value, depth + 1)
+            }
+            None => Err(self.error(loc, format!("unknown symbol {}", term))),
+        }
+    }
+}
+
+fn operand(arg: &Arg) -> Operand<'_> {
+    let upper = arg.text.to_ascii_uppercase();
+    match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::Hf,
+        "B" => Operand::B,
+        "R" => Operand::R,
+        _ if upper.len() == 2 && upper.starts_with('V') => {
+            match u16::from_str_radix(&upper[1..], 16) {
+                Ok(x) => Operand::V(x),
+                Err(_) => Operand::Value(arg),
+            }
+        }
+        _ if upper.starts_with("LONG ") => {
+            let value = arg.text[5..].trim_start();
+            Operand::Long(Arg {
+                text: value.to_string(),
+                loc: Loc {
+                    column: arg.loc.column + arg.text.len() - value.len(),
+                    ..arg.loc
+                },
+            })
+        }
+        _ => Operand::Value(arg),
+    }
+}
+
+fn item_is_empty(item: &Item) -> bool {
+    match item {
+        Item::Bytes(args) | Item::Words(args) => args.is_empty(),
+        Item::Instruction { .. } => false,
+    }
+}
+
+// `0x2a`, `0b101010` or `42`, with `_` allowed between digits.
+fn parse_number(s: &str) -> Option<i64> {
+    let s = s.replace('_', "");
+    let lower = s.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    // `;` inside a quoted include path isn't a comment
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn skip_space(text: &str, pos: usize) -> usize {
+    text[pos..]
+        .find(|c: char| !c.is_whitespace())
+        .map_or(text.len(), |n| pos + n)
+}
+
+// End of the identifier or number starting at `pos`.
+fn ident_end(text: &str, pos: usize) -> usize {
+    text[pos..]
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .map_or(text.len(), |n| pos + n)
+}
+
+#[cfg(test)]
+#[path = "./asm_tests.rs"]
+mod asm_tests;
@@ -0,0 +1,134 @@
+use super::*;
+use crate::cpu::Platform;
+use crate::disasm::disassemble;
+
+#[test]
+fn test_instructions() {
+    let source = "
+        CLS
+        LD V3, 0x10
+        ld va, vb
+        DRW V0, V1, 5
+        LD I, 0x2ea
+        LD I, LONG 0x1234
+        JP V0, 0x300
+        ADD V0, -1
+        SHR V2
+        LD [I], V1
+        LD V2, [I]
+        SCD 4
+        PLANE 3
+    ";
+    assert_eq!(
+        assemble(source).unwrap(),
+        vec![
+            0x00, 0xE0, 0x63, 0x10, 0x8A, 0xB0, 0xD0, 0x15, 0xA2, 0xEA, 0xF0, 0x00, 0x12, 0x34,
+            0xB3, 0x00, 0x70, 0xFF, 0x82, 0x26, 0xF1, 0x55, 0xF2, 0x65, 0x00, 0xC4, 0xF3, 0x01,
+        ]
+    );
+}
+
+#[test]
+fn test_labels_constants_data() {
+    let source = "
+        SPEED = 3
+        OFFSET = SPEED + 1   ; constants can use constants
+    start:
+        LD V0, SPEED
+        LD I, sprite + OFFSET
+        JP start
+    sprite: db 0b1111_0000, 0x90
+        dw start, 0x1234
+    ";
+    assert_eq!(
+        assemble(source).unwrap(),
+        vec![0x60, 0x03, 0xA2, 0x0A, 0x12, 0x00, 0xF0, 0x90, 0x02, 0x00, 0x12, 0x34]
+    );
+}
+
+#[test]
+fn test_errors() {
+    let err = |source: &str| assemble(source).unwrap_err();
+
+    let e = err("CLS\n    LD V0, 0x100");
+    assert_eq!((e.line, e.column), (2, 12));
+    assert!(e.message.contains("out of range"), "{}", e);
+
+    let e = err("  FOO V1");
+    assert_eq!((e.line, e.column), (1, 3));
+    assert_eq!(e.message, "unknown instruction FOO");
+
+    let e = err("DRW V0, V1");
+    assert_eq!(e.message, "DRW doesn't take these operands");
+
+    let e = err("JP nowhere");
+    assert_eq!((e.line, e.column), (1, 4));
+    assert_eq!(e.message, "unknown symbol nowhere");
+
+    let e = err("a:\na: CLS");
+    assert_eq!((e.line, e.column), (2, 1));
+
+    let e = err("db 1,, 2");
+    assert_eq!(e.column, 6);
+
+    let e = err("X = Y\nY = X\nLD V0, X");
+    assert!(e.message.contains("itself"), "{}", e);
+
+    // Only a lone name in front of `=` defines a constant
+    let e = err("LD V0, 1 = 2");
+    assert_eq!((e.line, e.column), (1, 10));
+    assert_eq!(e.message, "unexpected '='");
+
+    // 0xFE00 bytes fill memory up to 0xFFFF
+    let e = err(&("db 0\n".repeat(0xFE00) + "end:"));
+    assert_eq!((e.line, e.column), (0xFE01, 1));
+    assert_eq!(e.message, "the label doesn't fit in memory");
+
+    let e = err("BIG = 0x7fff_ffff_ffff_ffff\nLD V0, 1 + BIG");
+    assert_eq!((e.line, e.column), (2, 8));
+    assert_eq!(e.message, "1 + BIG overflows");
+
+    assert!(err("k: CLS").message.contains("reserved"));
+    assert_eq!(
+        err("CLS\nJP 0x1000").to_string(),
+        "<input>:2:4: 4096 is out of range (0 to 0xfff)"
+    );
+}
+
+#[test]
+fn test_include() {
+    let dir = std::env::temp_dir().join("chip8emu_asm_test");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.asm"), "include \"lib.asm\"\nJP lib\n").unwrap();
+    fs::write(dir.join("lib.asm"), "lib: RET\n  bad\n").unwrap();
+    let e = assemble_file(dir.join("main.asm")).unwrap_err();
+    assert!(e.file.ends_with("lib.asm"), "{}", e);
+    assert_eq!((e.line, e.column), (2, 3));
+
+    fs::write(dir.join("lib.asm"), "lib: RET\n").unwrap();
+    assert_eq!(
+        assemble_file(dir.join("main.asm")).unwrap(),
+        vec![0x00, 0xEE, 0x12, 0x00]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_round_trip() {
+    let roms: [&[u8]; 3] = [
+        include_bytes!("../pong.rom"),
+        include_bytes!("../test_opcode.ch8"),
+        include_bytes!("../Rocket Launcher.ch8"),
+    ];
+    for rom in roms.iter() {
+        let source = disassemble(rom, Platform::Chip8);
+        assert_eq!(&assemble(&source).unwrap()[..], *rom);
+    }
+
+    // XO-CHIP long loads, odd lengths and data that looks like code
+    let rom = [
+        0xF0, 0x00, 0x02, 0x08, 0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x0A, 0xFF,
+    ];
+    let source = disassemble(&rom, Platform::XoChip);
+    assert_eq!(assemble(&source).unwrap(), rom.to_vec());
+}
@@ -0,0 +1,182 @@
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+/// Shape of the beep played while the sound timer is running.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Waveform, String> {
+        match s {
+            "square" => Ok(Waveform::Square),
+            "triangle" => Ok(Waveform::Triangle),
+            "sawtooth" => Ok(Waveform::Sawtooth),
+            "sine" => Ok(Waveform::Sine),
+            _ => Err(format!("unknown waveform {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Waveform::Square => "square",
+            Waveform::Triangle => "triangle",
+            Waveform::Sawtooth => "sawtooth",
+            Waveform::Sine => "sine",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What the buzzer sounds like.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tone {
+    /// Pitch in Hz.
+    pub frequency: f32,
+    pub waveform: Waveform,
+    /// Amplitude from 0.0 (mute) to 1.0.
+    pub volume: f32,
+}
+
+impl Default for Tone {
+    fn default() -> Tone {
+        Tone {
+            frequency: 440.0,
+            waveform: Waveform::Square,
+            volume: 0.25,
+        }
+    }
+}
+
+impl Tone {
+    /// The sample at `phase` through one period (0.0 - 1.0), scaled by the volume.
+    pub fn sample(&self, phase: f32) -> f32 {
+        let p = phase.fract();
+        let s = match self.waveform {
+            Waveform::Square => {
+                if p < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (p - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * p - 1.0,
+            Waveform::Sine => (2.0 * PI * p).sin(),
+        };
+        s * self.volume.clamp(0.0, 1.0)
+    }
+}
+
+/// An XO-CHIP audio pattern: a loop of 128 1-bit samples.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    /// The samples, most significant bit of the first byte first.
+    pub bits: [u8; 16],
+    /// Playback pitch set by `FX3A`, 64 being 4000 samples per second.
+    pub pitch: u8,
+}
+
+impl Pattern {
+    /// Samples played per second.
+    pub fn rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    /// How many times per second the whole 128 sample loop plays.
+    pub fn frequency(&self) -> f32 {
+        self.rate() / 128.0
+    }
+
+    /// The sample at `phase` through the loop (0.0 - 1.0), scaled by `volume`.
+    pub fn sample(&self, phase: f32, volume: f32) -> f32 {
+        let bit = ((phase.fract() * 128.0) as usize).min(127);
+        let on = (self.bits[bit / 8] >> (7 - bit % 8)) & 1 == 1;
+        let s = if on { 1.0 } else { -1.0 };
+        s * volume.clamp(0.0, 1.0)
+    }
+}
+
+/// Something that can make the CHIP-8 buzz.
+///
+/// [`frame`](Beeper::frame) is called once per 60 Hz frame, after the timers
+/// ticked, with whether the sound timer is still running
+/// ([`Cpu::sound_active`](crate::cpu::Cpu::sound_active)).
+pub trait Beeper {
+    fn frame(&mut self, on: bool);
+
+    fn set_tone(&mut self, tone: Tone);
+
+    /// Plays `pattern` instead of the tone, or goes back to the tone on `None`.
+    /// Fed from [`Cpu::audio_pattern`](crate::cpu::Cpu::audio_pattern).
+    fn set_pattern(&mut self, pattern: Option<Pattern>);
+}
+
+/// A change of the buzzer state, as seen by [`HeadlessBeeper`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Transition {
+    /// Frame the change happened in, counting from 0.
+    pub frame: u64,
+    pub on: bool,
+}
+
+/// A silent [`Beeper`] that records when the buzzer turns on and off, for
+/// tests and machines without audio.
+#[derive(Clone, Debug, Default)]
+pub struct HeadlessBeeper {
+    pub tone: Tone,
+    pub pattern: Option<Pattern>,
+    pub transitions: Vec<Transition>,
+    frames: u64,
+    on: bool,
+}
+
+impl HeadlessBeeper {
+    pub fn new() -> HeadlessBeeper {
+        HeadlessBeeper::default()
+    }
+
+    /// Whether the buzzer is sounding as of the last frame.
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Number of frames seen so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
+
+impl Beeper for HeadlessBeeper {
+    fn frame(&mut self, on: bool) {
+        if on != self.on {
+            self.transitions.push(Transition {
+                frame: self.frames,
+                on,
+            });
+            self.on = on;
+        }
+        self.frames += 1;
+    }
+
+    fn set_tone(&mut self, tone: Tone) {
+        self.tone = tone;
+    }
+
+    fn set_pattern(&mut self, pattern: Option<Pattern>) {
+        self.pattern = pattern;
+    }
+}
+
+#[cfg(test)]
+#[path = "./audio_tests.rs"]
+mod audio_tests;
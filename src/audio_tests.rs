@@ -0,0 +1,49 @@
+use super::*;
+use crate::cpu::Cpu;
+use crate::quirks::Quirks;
+
+#[test]
+fn test_waveforms() {
+    let mut tone = Tone {
+        frequency: 440.0,
+        waveform: Waveform::Square,
+        volume: 1.0,
+    };
+    assert_eq!(tone.sample(0.25), 1.0);
+    assert_eq!(tone.sample(0.75), -1.0);
+    tone.waveform = Waveform::Triangle;
+    assert_eq!(tone.sample(0.5), 1.0);
+    assert_eq!(tone.sample(0.0), -1.0);
+    tone.waveform = Waveform::Sawtooth;
+    assert_eq!(tone.sample(0.0), -1.0);
+    tone.volume = 0.5;
+    tone.waveform = Waveform::Sine;
+    assert!((tone.sample(0.25) - 0.5).abs() < 1e-6);
+    assert_eq!("triangle".parse::<Waveform>(), Ok(Waveform::Triangle));
+    assert!("noise".parse::<Waveform>().is_err());
+}
+
+#[test]
+fn test_headless_transitions() {
+    // 0x200: LD V0, 3 / LD ST, V0 / JP 0x204
+    let mut cpu = Cpu::initialize(&[0x60, 0x03, 0xf0, 0x18, 0x12, 0x04], Quirks::default());
+    let mut beeper = HeadlessBeeper::new();
+    for _ in 0..6 {
+        cpu.run_frame().unwrap();
+        beeper.frame(cpu.sound_active());
+    }
+    // set to 3 in frame 0 and ticked at the end of frames 0, 1 and 2, so
+    // it sounds for three frames
+    assert_eq!(
+        beeper.transitions,
+        vec![
+            Transition { frame: 0, on: true },
+            Transition {
+                frame: 3,
+                on: false
+            },
+        ]
+    );
+    assert!(!beeper.is_on());
+    assert_eq!(beeper.frames(), 6);
+}
@@ -1,25 +1,135 @@
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cpu::Platform;
+use crate::disasm::{Disassembly, ROM_START};
+
+/// Why a ROM couldn't be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    /// The file is empty.
+    Empty,
+    /// The ROM doesn't fit in the memory of `platform` after 0x200.
+    TooLarge {
+        size: usize,
+        max: usize,
+        platform: Platform,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Empty => write!(f, "the ROM is empty"),
+            LoadError::TooLarge {
+                size,
+                max,
+                platform,
+            } => write!(
+                f,
+                "the ROM is {} bytes, {} only has room for {}",
+                size, platform, max
+            ),
+        }
+    }
+}
+
+impl error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> LoadError {
+        LoadError::Io(e)
+    }
+}
+
+/// A ROM image and what could be learned about it.
 #[derive(Clone, Debug)]
 pub struct Cartridge {
+    /// Raw program bytes, to be loaded at 0x200.
     pub rom: Vec<u8>,
+    /// File name the ROM was read from.
+    pub name: String,
+    /// SHA-1 of the program bytes.
+    pub sha1: [u8; 20],
+    /// Oldest platform whose instruction set covers the code found in the
+    /// ROM, or that has room for it.
+    pub platform: Platform,
 }
 
 impl Cartridge {
-    pub fn new(filename: &str) -> Cartridge {
-        println!("Trying to open {:?}", filename);
-        match std::fs::read(filename) {
-            Ok(bytes) => Cartridge { rom: bytes },
-            Err(e) => {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    eprintln!(
-                        "ERROR OPENING {}: are you sure the filename is correct?",
-                        filename
-                    );
-                }
-                if e.kind() == std::io::ErrorKind::PermissionDenied {
-                    eprintln!("Please run again with appropriate permissions.");
-                }
-                panic!("{}", e);
-            }
+    /// Reads the ROM at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Cartridge, LoadError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        );
+        Cartridge::from_bytes(&name, bytes)
+    }
+
+    /// Wraps ROM bytes that didn't come from a file, `name` standing in for
+    /// the file name. Fails if no platform has room for them.
+    pub fn from_bytes(name: &str, rom: Vec<u8>) -> Result<Cartridge, LoadError> {
+        if rom.is_empty() {
+            return Err(LoadError::Empty);
         }
+        let platform = detect_platform(&rom);
+        let c = Cartridge {
+            sha1: sha1_smol::Sha1::from(&rom).digest().bytes(),
+            name: name.to_string(),
+            rom,
+            platform,
+        };
+        c.check_fits(platform)?;
+        Ok(c)
+    }
+
+    /// Size of the ROM in bytes.
+    pub fn size(&self) -> usize {
+        self.rom.len()
+    }
+
+    /// The SHA-1 as 40 lowercase hex digits.
+    pub fn sha1_hex(&self) -> String {
+        self.sha1.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Fails if the ROM doesn't fit in `platform`'s memory.
+    pub fn check_fits(&self, platform: Platform) -> Result<(), LoadError> {
+        let max = platform.memory_size() - ROM_START as usize;
+        if self.rom.len() > max {
+            return Err(LoadError::TooLarge {
+                size: self.rom.len(),
+                max,
+                platform,
+            });
+        }
+        Ok(())
+    }
+}
+
+// Traces the code reachable from 0x200 with the largest instruction set and
+// picks the oldest platform that has all of it and room for the ROM.
+fn detect_platform(rom: &[u8]) -> Platform {
+    if rom.len() > Platform::SuperChip.memory_size() - ROM_START as usize {
+        return Platform::XoChip;
+    }
+    let code = Disassembly::trace(rom, Platform::XoChip).code;
+    if code.values().any(|ins| ins.is_xochip()) {
+        Platform::XoChip
+    } else if code.values().any(|ins| ins.is_schip()) {
+        Platform::SuperChip
+    } else {
+        Platform::Chip8
     }
 }
+
+#[cfg(test)]
+#[path = "./cartridge_tests.rs"]
+mod cartridge_tests;
@@ -0,0 +1,65 @@
+use super::*;
+
+#[test]
+fn test_load() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/pong.rom");
+    let c = Cartridge::load(path).unwrap();
+    assert_eq!(c.name, "pong.rom");
+    assert_eq!(c.size(), include_bytes!("../pong.rom").len());
+    assert_eq!(c.platform, Platform::Chip8);
+    assert_eq!(c.sha1_hex().len(), 40);
+    assert_eq!(
+        c.sha1_hex(),
+        sha1_smol::Sha1::from(&c.rom).digest().to_string()
+    );
+
+    match Cartridge::load("no such rom.ch8") {
+        Err(LoadError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+        r => panic!("expected an I/O error, got {:?}", r),
+    }
+}
+
+#[test]
+fn test_detect_platform() {
+    let detect = |rom: &[u8]| {
+        Cartridge::from_bytes("test", rom.to_vec())
+            .unwrap()
+            .platform
+    };
+    // 6001 1202
+    assert_eq!(detect(&[0x60, 0x01, 0x12, 0x02]), Platform::Chip8);
+    // 00FF 00FD
+    assert_eq!(detect(&[0x00, 0xFF, 0x00, 0xFD]), Platform::SuperChip);
+    // F002 1202
+    assert_eq!(detect(&[0xF0, 0x02, 0x12, 0x02]), Platform::XoChip);
+    // Unreachable bytes don't count: 1204 00FF 1204
+    assert_eq!(
+        detect(&[0x12, 0x04, 0x00, 0xFF, 0x12, 0x04]),
+        Platform::Chip8
+    );
+}
+
+#[test]
+fn test_size_checks() {
+    assert!(matches!(
+        Cartridge::from_bytes("empty", Vec::new()),
+        Err(LoadError::Empty)
+    ));
+
+    // Too big for CHIP-8, fine for XO-CHIP
+    let c = Cartridge::from_bytes("big", vec![0x12; 0x1000]).unwrap();
+    assert_eq!(c.platform, Platform::XoChip);
+    match c.check_fits(Platform::Chip8) {
+        Err(e @ LoadError::TooLarge { .. }) => assert_eq!(
+            e.to_string(),
+            "the ROM is 4096 bytes, chip8 only has room for 3584"
+        ),
+        r => panic!("expected TooLarge, got {:?}", r),
+    }
+    assert!(c.check_fits(Platform::XoChip).is_ok());
+
+    assert!(matches!(
+        Cartridge::from_bytes("huge", vec![0; 0x10000]),
+        Err(LoadError::TooLarge { max: 0xFE00, .. })
+    ));
+}
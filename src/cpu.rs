@@ -1,41 +1,183 @@
-use rand::Rng;
-use std::fs::OpenOptions;
-use std::io::prelude::*;
+use std::error;
+use std::fmt;
+
+use crate::audio::Pattern;
+use crate::font::{BIG_FONT_SET, FONT_SET};
+use crate::instruction::{decode_long, Instruction};
+use crate::quirks::{LoadStore, Quirks};
+use crate::random::{self, Random};
+
+/// Width of the screen in pixels.
+pub const WIDTH: u32 = 64;
+/// Height of the screen in pixels.
+pub const HEIGHT: u32 = 32;
+/// Width of the SUPER-CHIP high resolution screen.
+pub const HIRES_WIDTH: u32 = 128;
+/// Height of the SUPER-CHIP high resolution screen.
+pub const HIRES_HEIGHT: u32 = 64;
+
+/// Addressable memory on CHIP-8 and SUPER-CHIP.
+pub const MEMORY_SIZE: usize = 0x1000;
+/// Addressable memory on XO-CHIP.
+pub const XO_MEMORY_SIZE: usize = 0x10000;
+
+/// Address the big SUPER-CHIP font is loaded at, right after the small one.
+pub const BIG_FONT_ADDR: u16 = 0x50;
+
+/// Default number of nested subroutine calls, as on most interpreters.
+pub const STACK_DEPTH: usize = 16;
+/// Deepest stack [`Cpu::set_stack_depth`] allows.
+pub const MAX_STACK_DEPTH: usize = 64;
+
+/// Rate the delay and sound timers count down at, and so the frame rate.
+pub const TIMER_HZ: u32 = 60;
+/// Default instruction rate, close to what most games were tuned for.
+pub const DEFAULT_CLOCK_HZ: u32 = 700;
+
+/// Which instruction set a ROM was written for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Platform {
+    /// The original COSMAC VIP CHIP-8.
+    Chip8,
+    /// SUPER-CHIP 1.1: 128x64 mode, scrolling, big sprites and fonts.
+    SuperChip,
+    /// XO-CHIP: SUPER-CHIP plus 64 KiB of memory, two bitplanes and audio
+    /// patterns.
+    XoChip,
+}
+
+impl Platform {
+    /// Bytes of memory a ROM can address.
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => MEMORY_SIZE,
+            Platform::XoChip => XO_MEMORY_SIZE,
+        }
+    }
+
+    /// Whether `ins` is part of this platform's instruction set.
+    pub fn supports(&self, ins: &Instruction) -> bool {
+        match self {
+            Platform::Chip8 => !ins.is_schip() && !ins.is_xochip(),
+            Platform::SuperChip => !ins.is_xochip(),
+            Platform::XoChip => true,
+        }
+    }
+}
+
+impl std::str::FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Platform, String> {
+        match s {
+            "chip8" => Ok(Platform::Chip8),
+            "schip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(format!("unknown platform {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    /// The name [`from_str`](Platform::from_str) parses.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Platform::Chip8 => "chip8",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+        })
+    }
+}
+
+/// What an instruction touching memory past the end of the address space
+/// does, e.g. `FX55` with `I` near 0xFFF.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MemoryPolicy {
+    /// The address wraps around to the start of memory.
+    #[default]
+    Wrap,
+    /// The instruction faults with [`Fault::MemoryOutOfRange`].
+    Fault,
+    /// Reads give 0 and writes are dropped.
+    Ignore,
+}
 
-use crate::display;
+impl std::str::FromStr for MemoryPolicy {
+    type Err = String;
 
+    fn from_str(s: &str) -> Result<MemoryPolicy, String> {
+        match s {
+            "wrap" => Ok(MemoryPolicy::Wrap),
+            "fault" => Ok(MemoryPolicy::Fault),
+            "ignore" => Ok(MemoryPolicy::Ignore),
+            _ => Err(format!("unknown memory policy {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for MemoryPolicy {
+    /// The name [`from_str`](MemoryPolicy::from_str) parses.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            MemoryPolicy::Wrap => "wrap",
+            MemoryPolicy::Fault => "fault",
+            MemoryPolicy::Ignore => "ignore",
+        })
+    }
+}
+
+/// The whole CHIP-8 machine: memory, registers, timers and framebuffer.
 #[derive(Copy, Clone, Debug)]
 pub struct Cpu {
-    opcode: u8,         // Chip8 has 35 opcodes
-    memory: [u8; 4096], // 4096 locations 8bit each
-    v: [u8; 16],        // 15 registers + 16th "carry flag" register
-    i: u16,             // Index Register
-    pc: u16,            // Program Counter 0x00f - 0xfff
-    sp: u16,            // Stack Pointer
-    stack: [u16; 12],   // Stack
-    delay_timer: u8,    // Counters count at 60hz.
-    sound_timer: u8,    // When set above zero, they will count down.
-    wait_key: bool,     // CPU waiting for key press
-    pub gfx: [[u8; (display::WIDTH as usize)]; (display::HEIGHT as usize)], // 2048 pixels monochrone (1-on, 0-off)
+    pub(crate) opcode: u16,                   // Current opcode
+    pub(crate) memory: [u8; XO_MEMORY_SIZE],  // 4096 locations 8bit each, 64 KiB on XO-CHIP
+    pub(crate) v: [u8; 16],                   // 15 registers + 16th "carry flag" register
+    pub(crate) i: u16,                        // Index Register
+    pub(crate) pc: u16,                       // Program Counter 0x00f - 0xfff
+    pub(crate) sp: u16,                       // Stack Pointer
+    pub(crate) stack: [u16; MAX_STACK_DEPTH], // Return addresses, pc + 2 of each call
+    pub(crate) stack_depth: usize,            // Calls allowed before a stack overflow
+    pub(crate) delay_timer: u8,               // Counters count at 60hz.
+    pub(crate) sound_timer: u8,               // When set above zero, they will count down.
+    pub(crate) wait_key: bool,                // CPU waiting for key press
+    pub(crate) wait_reg: usize,               // Register FX0A stores the key in
+    pub(crate) wait_held: Option<u8>,         // Key pressed while waiting, stored once released
+    pub(crate) keypad: [bool; 16],            // Hex keypad 0x0 - 0xF, true while held down
+    pub(crate) clock_hz: u32,                 // Instructions executed per second
+    pub(crate) cycle_acc: u32,                // Leftover clock ticks that didn't make a whole frame
+    pub(crate) platform: Platform,            // Instruction set in use
+    pub(crate) memory_policy: MemoryPolicy,   // Out of range memory accesses
+    pub(crate) hires: bool,                   // SCHIP 128x64 mode
+    pub(crate) halted: bool,                  // Set by 00FD, nothing runs afterwards
+    pub(crate) rpl: [u8; 16],                 // SCHIP RPL user flags saved by FX75
+    pub(crate) planes: u8,                    // XO-CHIP bitplanes selected by FN01
+    pub(crate) audio: [u8; 16],               // XO-CHIP audio pattern loaded by F002
+    pub(crate) pitch: u8,                     // XO-CHIP audio pattern pitch set by FX3A
+    pub(crate) audio_loaded: bool,            // Whether F002 ran, until then the plain tone plays
+    pub(crate) quirks: Quirks,                // Behaviour of the ambiguous opcodes
+    pub(crate) rom_hash: [u8; 20],            // SHA-1 of the loaded ROM
+    pub(crate) rng: Random,                   // Source of CXNN's random bytes
+    pub(crate) buzzed: bool,                  // Sound timer was running at the last tick
+    /// Pixels indexed as `gfx[y][x]`, one bit per bitplane: 0 is off, 1 is
+    /// on in plane 1. XO-CHIP adds plane 2 (2) and both planes (3). Only the
+    /// top-left [`width`](Cpu::width) x [`height`](Cpu::height) are in use.
+    pub gfx: [[u8; HIRES_WIDTH as usize]; HIRES_HEIGHT as usize],
 }
 
 impl Cpu {
-    // Initialize registers and memory once
-    pub fn initialize(c: &Vec<u8>) -> Cpu {
-        let mut m: [u8; 4096] = [0; 4096];
-        for i in 0..80 {
-            m[i] = display::FONT_SET[i];
-        }
+    /// Creates a machine with the font loaded at 0x000 and `c` loaded at
+    /// 0x200, ready to execute from 0x200, with ambiguous opcodes behaving
+    /// as `quirks` says. Bytes of `c` past the end of memory are dropped;
+    /// [`Cartridge`](crate::cartridge::Cartridge) checks that a ROM fits.
+    pub fn initialize(c: &[u8], quirks: Quirks) -> Cpu {
+        let mut m: [u8; XO_MEMORY_SIZE] = [0; XO_MEMORY_SIZE];
+        m[..FONT_SET.len()].copy_from_slice(&FONT_SET);
+        let big = BIG_FONT_ADDR as usize;
+        m[big..big + BIG_FONT_SET.len()].copy_from_slice(&BIG_FONT_SET);
 
         // Loads cartridge data starting from RAM address 0x200
-        let mut last = 0;
-        for (i, &el) in c.iter().enumerate() {
-            m[0x200 + i] = el;
-            if 0x200 + i > last {
-                last = 0x200 + i;
-            }
-        }
-        println!("last: {:#0x}", last);
+        let len = c.len().min(XO_MEMORY_SIZE - 0x200);
+        m[0x200..0x200 + len].copy_from_slice(&c[..len]);
 
         Cpu {
             opcode: 0,
@@ -44,450 +186,841 @@ impl Cpu {
             i: 0,
             pc: 0x200,
             sp: 0,
-            stack: [0; 12],
+            stack: [0; MAX_STACK_DEPTH],
+            stack_depth: STACK_DEPTH,
             delay_timer: 0,
             sound_timer: 0,
             wait_key: false,
-            gfx: [[0; (display::WIDTH as usize)]; (display::HEIGHT as usize)],
-        }
-    }
-
-    // Reads memory from 0x200 until it finds an empty address
-    pub fn dump_memory(&self, to_file: bool) {
-        for e in (0..(0x1000 - 0x200)).step_by(2) {
-            let opcode: u16 = (self.memory[self.pc as usize + e] as u16) << 8
-                | (self.memory[self.pc as usize + e + 1] as u16);
-            if opcode == 0 {
-                break;
-            }
-
-            if to_file {
-                let a = format!("{:#0x}", opcode);
-                write_to_file(&a[..]);
-            } else {
-                println!("{:#0x}", opcode);
-            }
+            wait_reg: 0,
+            wait_held: None,
+            keypad: [false; 16],
+            clock_hz: DEFAULT_CLOCK_HZ,
+            cycle_acc: 0,
+            platform: Platform::Chip8,
+            memory_policy: MemoryPolicy::Wrap,
+            hires: false,
+            halted: false,
+            rpl: [0; 16],
+            planes: 1,
+            audio: [0; 16],
+            pitch: 64,
+            audio_loaded: false,
+            quirks,
+            rom_hash: sha1_smol::Sha1::from(c).digest().bytes(),
+            rng: Random::new(random::entropy_seed()),
+            buzzed: false,
+            gfx: [[0; HIRES_WIDTH as usize]; HIRES_HEIGHT as usize],
         }
     }
 
+    /// Draws the font glyph for `digit` straight into `gfx` at the given offset.
     pub fn print_digit(&mut self, digit: u8, offset_x: u8, offset_y: u8) {
-        let a: &[u8] = &display::FONT_SET[(digit * 5) as usize..(digit * 5 + 5) as usize];
+        let a: &[u8] = &FONT_SET[(digit * 5) as usize..(digit * 5 + 5) as usize];
         let and_mask: u8 = 128;
 
-        let mut x = 0;
-        for i in offset_y..(offset_y + 5) {
-            let mut temp = a[x as usize];
-            x += 1;
-            // println!("{:#01x}", a[i]);
+        for (x, i) in (offset_y..(offset_y + 5)).enumerate() {
+            let mut temp = a[x];
             for e in offset_x..(offset_x + 8) {
                 let last_bit: u8 = temp & and_mask;
-                // println!("{}", last_bit);
                 if last_bit == 128 {
                     self.gfx[i as usize][e as usize] = 1;
                 }
-                temp = temp << 1;
+                temp <<= 1;
             }
         }
     }
 
-    pub fn print_state(&self, key: u8) {
-        let flag = if self.wait_key { 1 } else { 0 };
-        println!(
-            "i: {:#0x}  pc: {:#0x}  sp:{:#0x}  flag:{:#0x}  key:{}",
-            self.i, self.pc, self.sp, flag, key
-        );
-        print!("v: ");
-        for i in 0..15 {
-            print!(" {:#0x}", self.v[i]);
-        }
-        println!();
-        print!("stack: ");
-        for i in 0..12 {
-            print!(" {:#0x}", self.stack[i]);
-        }
-        println!();
-        print!("mem[i]: ");
-        for i in 0..16 {
-            print!(" {:#0x}", self.memory[self.i as usize + i]);
-        }
-        println!();
-        println!();
+    /// Holds down hex key `key` (0x0 - 0xF) until [`release_key`](Cpu::release_key).
+    pub fn press_key(&mut self, key: u8) {
+        self.keypad[(key & 0xF) as usize] = true;
     }
 
-    pub fn emulate_instruction(&mut self, opcode: u16, key: u8) {
-        let op1: u8 = (opcode >> 8) as u8;
-        let op2: u8 = (opcode & 0x00FF) as u8;
-        self.memory[self.pc as usize] = op1;
-        self.memory[self.pc as usize + 1] = op2;
-        self.emulate_cycle(key);
+    /// Lets go of hex key `key` (0x0 - 0xF).
+    pub fn release_key(&mut self, key: u8) {
+        self.keypad[(key & 0xF) as usize] = false;
     }
 
-    pub fn emulate_cycle(&mut self, key: u8) {
-        let opcode: u16 = (self.memory[self.pc as usize] as u16) << 8
-            | (self.memory[self.pc as usize + 1] as u16);
-        let nnn: u16 = opcode & 0x0FFF;
+    /// Whether hex key `key` (0x0 - 0xF) is currently held down.
+    pub fn is_key_pressed(&self, key: u8) -> bool {
+        self.keypad[(key & 0xF) as usize]
+    }
 
-        println!("opcode: {:#0x}", opcode);
+    /// The instruction set being emulated.
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
 
-        if !self.wait_key || (self.wait_key && key != 0) {
-            // Update timers
-            if !self.wait_key {
-                if self.delay_timer > 0 {
-                    self.delay_timer -= 1;
-                }
-                if self.sound_timer > 0 {
-                    self.sound_timer -= 1;
-                }
-            }
+    /// Switches the instruction set. SUPER-CHIP instructions fault with
+    /// [`Fault::InvalidOpcode`] unless the platform supports them.
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+    }
 
-            // All opcodes use the first 4 bits to specify what command
-            // it is. The remaining 12 bits are arguments.
-            match opcode & 0xF000 {
-                0x0000 => self.op_0(nnn),
-                0x1000 => self.op_1(nnn),
-                0x2000 => self.op_2(nnn),
-                0x3000 => self.op_3(nnn),
-                0x4000 => self.op_4(nnn),
-                0x5000 => self.op_5(nnn),
-                0x6000 => self.op_6(nnn),
-                0x7000 => self.op_7(nnn),
-                0x8000 => self.op_8(nnn),
-                0x9000 => self.op_9(nnn),
-                0xA000 => self.op_a(nnn),
-                0xB000 => self.op_b(nnn),
-                0xC000 => self.op_c(nnn),
-                0xD000 => self.op_d(nnn),
-                0xE000 => self.op_e(nnn, key),
-                0xF000 => self.op_f(nnn, key),
-                _ => panic!("ERROR OPCODE NOT RECOGNIZED"),
-            }
+    /// What out of range memory accesses do.
+    pub fn memory_policy(&self) -> MemoryPolicy {
+        self.memory_policy
+    }
+
+    /// Changes what out of range memory accesses do, effective immediately.
+    pub fn set_memory_policy(&mut self, policy: MemoryPolicy) {
+        self.memory_policy = policy;
+    }
+
+    /// SHA-1 of the ROM the machine was initialized with.
+    pub fn rom_hash(&self) -> [u8; 20] {
+        self.rom_hash
+    }
+
+    /// How the ambiguous opcodes behave.
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Changes how the ambiguous opcodes behave, effective immediately.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Restarts the `CXNN` random numbers from `seed`, so runs seeded alike
+    /// see the same numbers. Until then the seed differs on every run.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Random::new(seed);
+    }
+
+    /// Number of nested subroutine calls allowed.
+    pub fn stack_depth(&self) -> usize {
+        self.stack_depth
+    }
+
+    /// Changes the number of nested calls allowed, from 1 to
+    /// [`MAX_STACK_DEPTH`]. A call beyond it faults with
+    /// [`Fault::StackOverflow`].
+    pub fn set_stack_depth(&mut self, depth: usize) {
+        self.stack_depth = depth.clamp(1, MAX_STACK_DEPTH);
+    }
+
+    /// Width of the screen in the current resolution.
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH as usize
         } else {
-            return;
+            WIDTH as usize
         }
     }
 
-    fn op_0(&mut self, nnn: u16) {
-        match nnn {
-            // Clears the screen
-            0x0E0 => {
-                for i in 0..(display::HEIGHT) as usize {
-                    for j in 0..(display::WIDTH) as usize {
-                        self.gfx[i][j] = 0;
-                    }
-                }
-                self.pc += 2;
-            }
+    /// Height of the screen in the current resolution.
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT as usize
+        } else {
+            HEIGHT as usize
+        }
+    }
 
-            // Returns from a subroutine
-            0x0EE => {
-                self.sp -= 1;
-                self.pc = self.stack[self.sp as usize];
-            }
+    /// Whether the ROM exited with `00FD`.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
 
-            _ => panic!("ERROR OP_0 NOT RECOGNIZED"),
+    /// The XO-CHIP audio pattern to play instead of the plain tone, once the
+    /// ROM loaded one with `F002`.
+    pub fn audio_pattern(&self) -> Option<Pattern> {
+        if self.audio_loaded {
+            Some(Pattern {
+                bits: self.audio,
+                pitch: self.pitch,
+            })
+        } else {
+            None
         }
     }
 
-    // Jump to address NNN
-    fn op_1(&mut self, nnn: u16) {
-        self.pc = nnn;
+    /// Instructions executed per second by [`run_frame`](Cpu::run_frame).
+    pub fn clock_hz(&self) -> u32 {
+        self.clock_hz
     }
 
-    // Calls subroutine at NNN
-    fn op_2(&mut self, nnn: u16) {
-        self.stack[self.sp as usize] = self.pc as u16 + 2;
-        self.sp += 1;
-        self.pc = nnn;
+    /// Changes the instruction rate, effective from the next frame.
+    pub fn set_clock_hz(&mut self, hz: u32) {
+        self.clock_hz = hz.max(1);
+        self.cycle_acc = 0;
     }
 
-    // Skips the next instruction if VX equals NN.
-    fn op_3(&mut self, nnn: u16) {
-        let x: usize = ((nnn & 0x0F00) >> 8) as usize;
-        let nn: u8 = (nnn & 0x00FF) as u8;
-        if self.v[x] == nn {
-            self.pc += 2;
+    /// Runs one 60 Hz frame: as many instructions as the clock rate allows,
+    /// then one tick of the timers.
+    ///
+    /// When the clock rate isn't a multiple of 60 the remainder is carried
+    /// over, so over a second exactly `clock_hz` instructions run.
+    pub fn run_frame(&mut self) -> Result<(), Fault> {
+        for _ in 0..self.frame_cycles() {
+            if self.halted {
+                break;
+            }
+            self.emulate_cycle()?;
         }
-        self.pc += 2;
+        self.tick_timers();
+        Ok(())
+    }
+
+    // Instructions due in the next frame, carrying the remainder over.
+    pub(crate) fn frame_cycles(&mut self) -> u32 {
+        self.cycle_acc += self.clock_hz;
+        let cycles = self.cycle_acc / TIMER_HZ;
+        self.cycle_acc %= TIMER_HZ;
+        cycles
     }
 
-    // Skips the next instruction if VX doesn't equal NN.
-    fn op_4(&mut self, nnn: u16) {
-        let x: usize = ((nnn & 0x0F00) >> 8) as usize;
-        let nn: u8 = (nnn & 0x00FF) as u8;
-        if self.v[x] != nn {
-            self.pc += 2;
+    /// Whether the buzzer should be sounding, i.e. the sound timer is running
+    /// or, after [`run_frame`](Cpu::run_frame), ran during that frame. A
+    /// sound timer of 1 sounds for the frame it reaches 0 in.
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0 || self.buzzed
+    }
+
+    /// Counts the delay and sound timers down by one, as happens at 60 Hz.
+    pub fn tick_timers(&mut self) {
+        self.buzzed = self.sound_timer > 0;
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
         }
-        self.pc += 2;
     }
 
-    // Skips the next instruction if VX equals VY.
-    fn op_5(&mut self, nnn: u16) {
-        let x: usize = ((nnn & 0x0F00) >> 8) as usize;
-        let y: usize = ((nnn & 0x00F0) >> 4) as usize;
-        if self.v[x] == self.v[y] {
-            self.pc += 2;
+    /// Writes `opcode` at the program counter and executes it.
+    pub fn emulate_instruction(&mut self, opcode: u16) -> Result<(), Fault> {
+        let op1: u8 = (opcode >> 8) as u8;
+        let op2: u8 = (opcode & 0x00FF) as u8;
+        let size = self.platform.memory_size();
+        self.memory[self.pc as usize % size] = op1;
+        self.memory[(self.pc as usize + 1) % size] = op2;
+        self.emulate_cycle()
+    }
+
+    /// Fetches, decodes and executes the instruction at the program counter.
+    ///
+    /// Timers are left alone, see [`run_frame`](Cpu::run_frame) and
+    /// [`tick_timers`](Cpu::tick_timers) for those. While an `FX0A` is
+    /// waiting for a key nothing is executed. On a
+    /// [`Fault`] the machine is left untouched, still pointing at the
+    /// faulting opcode.
+    pub fn emulate_cycle(&mut self) -> Result<(), Fault> {
+        let opcode = self.fetch(self.pc)?;
+        self.opcode = opcode;
+
+        if self.halted {
+            return Ok(());
         }
-        self.pc += 2;
+        if self.wait_key {
+            self.poll_wait_key();
+            return Ok(());
+        }
+
+        // Only F000 NNNN reads on, so the last word of memory can hold any
+        // other instruction
+        let next = if opcode == 0xF000 {
+            self.fetch(self.pc.wrapping_add(2))?
+        } else {
+            0
+        };
+        let ins = decode_long(opcode, next).map_err(|e| Fault::InvalidOpcode {
+            pc: self.pc,
+            opcode: e.opcode,
+        })?;
+        if !self.platform.supports(&ins) {
+            return Err(Fault::InvalidOpcode {
+                pc: self.pc,
+                opcode,
+            });
+        }
+
+        self.execute(ins)
     }
 
-    // Sets VX to NN.
-    fn op_6(&mut self, nnn: u16) {
-        let x: usize = ((nnn & 0x0F00) >> 8) as usize;
-        let nn: u8 = (nnn & 0x00FF) as u8;
-        self.v[x] = nn;
-        self.pc += 2;
+    // The big-endian word at `addr`, wrapping around the end of memory.
+    pub(crate) fn word_at(&self, addr: u16) -> u16 {
+        (self.peek(addr as usize) as u16) << 8 | self.peek(addr as usize + 1) as u16
     }
 
-    // Adds NN to VX. (Carry flag is not changed)
-    fn op_7(&mut self, nnn: u16) {
-        let x: usize = ((nnn & 0x0F00) >> 8) as usize;
-        let nn: u8 = (nnn & 0x00FF) as u8;
-        self.v[x] = self.v[x].wrapping_add(nn);
-        self.pc += 2;
+    // The byte at `addr`, wrapping around the end of memory. For looking at
+    // the machine, instructions go through `read` and `write`.
+    pub(crate) fn peek(&self, addr: usize) -> u8 {
+        self.memory[addr % self.platform.memory_size()]
     }
 
-    fn op_8(&mut self, nnn: u16) {
-        let x: usize = ((nnn & 0x0F00) >> 8) as usize;
-        let y: usize = ((nnn & 0x00F0) >> 4) as usize;
-        let z: usize = (nnn & 0x000F) as usize;
-        match z {
-            // Sets VX to the value of VY.
-            0 => self.v[x] = self.v[y],
+    // Where the byte `offset` bytes after `base` is under the memory policy,
+    // or None if the access is to be ignored.
+    fn locate(&self, base: u16, offset: usize) -> Result<Option<usize>, Fault> {
+        let addr = base as usize + offset;
+        let size = self.platform.memory_size();
+        if addr < size {
+            return Ok(Some(addr));
+        }
+        match self.memory_policy {
+            MemoryPolicy::Wrap => Ok(Some(addr % size)),
+            MemoryPolicy::Fault => Err(Fault::MemoryOutOfRange {
+                pc: self.pc,
+                addr: addr as u32,
+            }),
+            MemoryPolicy::Ignore => Ok(None),
+        }
+    }
 
-            // Sets VX to VX or VY. (Bitwise OR operation)
-            1 => self.v[x] |= self.v[y],
+    // Faults if any of the `len` bytes from `base` is out of range and the
+    // policy says so. Instructions touching several bytes call this first,
+    // so a fault leaves the machine untouched.
+    fn check_range(&self, base: u16, len: usize) -> Result<(), Fault> {
+        if len > 0 {
+            self.locate(base, len - 1)?;
+        }
+        Ok(())
+    }
 
-            // Sets VX to VX and VY. (Bitwise AND operation)
-            2 => self.v[x] &= self.v[y],
+    // The byte `offset` bytes after `base`, as an instruction reads it.
+    fn read(&self, base: u16, offset: usize) -> Result<u8, Fault> {
+        Ok(self.locate(base, offset)?.map_or(0, |a| self.memory[a]))
+    }
 
-            // Sets VX to VX xor VY.
-            3 => self.v[x] ^= self.v[y],
+    // Stores `value` `offset` bytes after `base`, as an instruction writes it.
+    fn write(&mut self, base: u16, offset: usize, value: u8) -> Result<(), Fault> {
+        if let Some(a) = self.locate(base, offset)? {
+            self.memory[a] = value;
+        }
+        Ok(())
+    }
 
-            // Adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't.
-            4 => {
-                let res = self.v[x] as u16 + self.v[y] as u16;
-                if res > 0xFF {
-                    self.v[0xf] = 1;
-                } else {
-                    self.v[0xf] = 0;
-                }
-                self.v[x] = res as u8;
-            }
+    // The big-endian instruction word at `addr`.
+    fn fetch(&self, addr: u16) -> Result<u16, Fault> {
+        self.check_range(addr, 2)?;
+        Ok((self.read(addr, 0)? as u16) << 8 | self.read(addr, 1)? as u16)
+    }
 
-            // VY is subtracted from VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
-            5 => {
-                let res = self.v[x] as i16 - self.v[y] as i16;
-                if res < 0x0 {
-                    self.v[0xf] = 0;
-                } else {
-                    self.v[0xf] = 1;
+    // FX0A completes like on the COSMAC VIP: a key has to be pressed and
+    // then released before it is stored and execution resumes.
+    fn poll_wait_key(&mut self) {
+        match self.wait_held {
+            None => self.wait_held = self.keypad.iter().position(|&k| k).map(|k| k as u8),
+            Some(k) => {
+                if !self.keypad[k as usize] {
+                    self.v[self.wait_reg] = k;
+                    self.wait_key = false;
+                    self.wait_held = None;
+                    self.pc = self.pc.wrapping_add(2);
                 }
-                self.v[x] = self.v[x].wrapping_sub(self.v[y]);
             }
+        }
+    }
 
-            // Stores the least significant bit of VX in VF and then shifts VX to the right by 1.
-            6 => {
-                self.v[0xf] = self.v[x] & 0b00000001;
-                self.v[x] >>= 1;
+    // Runs `ins`, checking anything that can fault before changing the
+    // machine.
+    fn execute(&mut self, ins: Instruction) -> Result<(), Fault> {
+        match ins {
+            // Clears the screen
+            Instruction::Cls => {
+                self.clear_screen();
+                self.pc = self.pc.wrapping_add(2);
             }
 
-            // Sets VX to VY minus VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
-            7 => {
-                let res = self.v[y] as i16 - self.v[x] as i16;
-                if res < 0x0 {
-                    self.v[0xf] = 0;
-                } else {
-                    self.v[0xf] = 1;
+            // Returns from a subroutine
+            Instruction::Ret => {
+                if self.sp == 0 {
+                    return Err(Fault::StackUnderflow { pc: self.pc });
                 }
-                self.v[x] = self.v[y].wrapping_sub(self.v[x]);
+                self.sp -= 1;
+                self.pc = self.stack[self.sp as usize];
             }
 
-            // Stores the most significant bit of VX in VF and then shifts VX to the left by 1.
-            14 => {
-                self.v[0xf] = (self.v[x] & 0b10000000) >> 7;
-                self.v[x] <<= 1;
+            // Scrolls the screen down N pixels
+            Instruction::Scd(n) => {
+                self.scroll(0, n as isize);
+                self.pc = self.pc.wrapping_add(2);
             }
 
-            _ => panic!("ERROR OP_8 NOT RECOGNIZED"),
-        }
-        self.pc += 2;
-    }
+            // Scrolls the screen up N pixels
+            Instruction::Scu(n) => {
+                self.scroll(0, -(n as isize));
+                self.pc = self.pc.wrapping_add(2);
+            }
 
-    // Skips the next instruction if VX doesn't equal VY.
-    fn op_9(&mut self, nnn: u16) {
-        let x: usize = ((nnn & 0x0F00) >> 8) as usize;
-        let y: usize = ((nnn & 0x00F0) >> 4) as usize;
-        if self.v[x] != self.v[y] {
-            self.pc += 2;
-        }
-        self.pc += 2;
-    }
+            // Scrolls the screen right 4 pixels
+            Instruction::Scr => {
+                self.scroll(4, 0);
+                self.pc = self.pc.wrapping_add(2);
+            }
 
-    // Sets I to the address NNN.
-    fn op_a(&mut self, nnn: u16) {
-        self.i = nnn;
-        self.pc += 2;
-    }
+            // Scrolls the screen left 4 pixels
+            Instruction::Scl => {
+                self.scroll(-4, 0);
+                self.pc = self.pc.wrapping_add(2);
+            }
 
-    // Jumps to the address NNN plus V0.
-    fn op_b(&mut self, nnn: u16) {
-        self.pc = self.v[0] as u16 + nnn;
-    }
+            // Exits the interpreter
+            Instruction::Exit => self.halted = true,
 
-    // Sets VX to the result of a bitwise and operation on a random number (Typically: 0 to 255) and NN.
-    fn op_c(&mut self, nnn: u16) {
-        let x: usize = ((nnn & 0x0F00) >> 8) as usize;
-        let nn: u8 = (nnn & 0x00FF) as u8;
+            // Switches to low or high resolution, clearing all planes
+            Instruction::Low | Instruction::High => {
+                self.hires = ins == Instruction::High;
+                self.gfx = [[0; HIRES_WIDTH as usize]; HIRES_HEIGHT as usize];
+                self.pc = self.pc.wrapping_add(2);
+            }
 
-        let mut rng = rand::thread_rng();
-        let r: u8 = rng.gen::<u8>();
-        self.v[x] = r & nn;
+            // Jump to address NNN
+            Instruction::Jp(nnn) => self.pc = nnn,
 
-        self.pc += 2;
-    }
+            // Calls subroutine at NNN
+            Instruction::Call(nnn) => {
+                if self.sp as usize >= self.stack_depth {
+                    return Err(Fault::StackOverflow { pc: self.pc });
+                }
+                self.stack[self.sp as usize] = self.pc.wrapping_add(2);
+                self.sp += 1;
+                self.pc = nnn;
+            }
 
-    // Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels and a height of N+1 pixels.
-    fn op_d(&mut self, nnn: u16) {
-        let x: usize = ((nnn & 0x0F00) >> 8) as usize;
-        let y: usize = ((nnn & 0x00F0) >> 4) as usize;
-        let n: usize = (nnn & 0x000F) as usize;
+            // Skips the next instruction if VX equals NN.
+            Instruction::SeByte { x, nn } => self.skip_if(self.v[x] == nn)?,
 
-        self.v[0xf] = 0;
+            // Skips the next instruction if VX doesn't equal NN.
+            Instruction::SneByte { x, nn } => self.skip_if(self.v[x] != nn)?,
 
-        for byte in 0..n {
-            let y: u8 = (self.v[y] + byte as u8) % display::HEIGHT as u8;
-            for b in 0..8 {
-                let x: u8 = (self.v[x] + b as u8) % display::WIDTH as u8;
-                let color: u8 = (self.memory[self.i as usize + byte] >> (7 - b)) & 1;
-                self.v[0x0f] |= color & self.gfx[y as usize][x as usize];
-                self.gfx[y as usize][x as usize] ^= color;
+            // Skips the next instruction if VX equals VY.
+            Instruction::SeReg { x, y } => self.skip_if(self.v[x] == self.v[y])?,
+
+            // Sets VX to NN.
+            Instruction::LdByte { x, nn } => {
+                self.v[x] = nn;
+                self.pc = self.pc.wrapping_add(2);
             }
-        }
 
-        self.pc += 2;
-    }
+            // Adds NN to VX. (Carry flag is not changed)
+            Instruction::AddByte { x, nn } => {
+                self.v[x] = self.v[x].wrapping_add(nn);
+                self.pc = self.pc.wrapping_add(2);
+            }
 
-    fn op_e(&mut self, nnn: u16, key: u8) {
-        let x: usize = ((nnn & 0x0F00) >> 8) as usize;
-        let y: usize = ((nnn & 0x00F0) >> 4) as usize;
+            Instruction::LdReg { .. }
+            | Instruction::Or { .. }
+            | Instruction::And { .. }
+            | Instruction::Xor { .. }
+            | Instruction::AddReg { .. }
+            | Instruction::Sub { .. }
+            | Instruction::Shr { .. }
+            | Instruction::Subn { .. }
+            | Instruction::Shl { .. } => {
+                self.alu(ins);
+                self.pc = self.pc.wrapping_add(2);
+            }
 
-        match y {
-            // Skips the next instruction if the key stored in VX is pressed.
-            9 => {
-                if self.v[x] == key {
-                    self.pc += 2;
+            // Stores VX to VY in memory starting at address I, in either order.
+            Instruction::SaveRange { x, y } => {
+                self.check_range(self.i, x.max(y) - x.min(y) + 1)?;
+                for (n, r) in register_range(x, y).enumerate() {
+                    self.write(self.i, n, self.v[r])?;
                 }
+                self.pc = self.pc.wrapping_add(2);
             }
 
-            // Skips the next instruction if the key stored in VX isn't pressed.
-            10 => {
-                if self.v[x] != key {
-                    self.pc += 2;
+            // Fills VX to VY with values from memory starting at address I.
+            Instruction::LoadRange { x, y } => {
+                self.check_range(self.i, x.max(y) - x.min(y) + 1)?;
+                for (n, r) in register_range(x, y).enumerate() {
+                    self.v[r] = self.read(self.i, n)?;
                 }
+                self.pc = self.pc.wrapping_add(2);
             }
 
-            _ => panic!("ERROR OP_E NOT RECOGNIZED"),
-        }
+            // Skips the next instruction if VX doesn't equal VY.
+            Instruction::SneReg { x, y } => self.skip_if(self.v[x] != self.v[y])?,
 
-        self.pc += 2;
-    }
+            // Sets I to the address NNN.
+            Instruction::LdI(nnn) => {
+                self.i = nnn;
+                self.pc = self.pc.wrapping_add(2);
+            }
 
-    fn op_f(&mut self, nnn: u16, key_pressed: u8) {
-        let x: usize = ((nnn & 0x0F00) >> 8) as usize;
-        let y: usize = ((nnn & 0x00F0) >> 4) as usize;
-        let z: usize = (nnn & 0x000F) as usize;
+            // Jumps to the address NNN plus V0.
+            // With the jump quirk it is XNN plus VX instead.
+            Instruction::JpV0(nnn) => {
+                let x = if self.quirks.jump_vx {
+                    (nnn >> 8) as usize
+                } else {
+                    0
+                };
+                self.pc = self.v[x] as u16 + nnn;
+            }
 
-        match y {
-            0 => match z {
-                // Sets VX to the value of the delay timer.
-                7 => {
-                    self.v[x] = self.delay_timer;
-                    self.pc += 2;
-                }
+            // Sets VX to the result of a bitwise and operation on a random number (Typically: 0 to 255) and NN.
+            Instruction::Rnd { x, nn } => {
+                let r = if self.quirks.vip_random {
+                    self.rng.next_vip_byte()
+                } else {
+                    self.rng.next_byte()
+                };
+                self.v[x] = r & nn;
+                self.pc = self.pc.wrapping_add(2);
+            }
 
-                // A key press is awaited, and then stored in VX.
-                10 => {
-                    println!("pressed: {}", key_pressed);
-                    self.wait_key = true;
-                    if key_pressed != 0 {
-                        self.v[x] = key_pressed;
-                        self.wait_key = false;
-                        self.pc += 2;
-                    }
+            // Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels and a height of N pixels.
+            // A height of 0 draws a 16x16 sprite instead, two bytes per row.
+            Instruction::Drw { x, y, n } => {
+                if n == 0 && self.platform != Platform::Chip8 {
+                    self.draw_sprite(self.v[x], self.v[y], 16, 16)?;
+                } else {
+                    self.draw_sprite(self.v[x], self.v[y], 8, n as usize)?;
                 }
+                self.pc = self.pc.wrapping_add(2);
+            }
 
-                _ => panic!("ERROR OP_F0 NOT RECOGNIZED"),
-            },
+            // Sets I to the 16-bit address in the next word.
+            Instruction::LdILong(nnnn) => {
+                self.i = nnnn;
+                self.pc = self.pc.wrapping_add(4);
+            }
 
-            1 => match z {
-                // Sets the delay timer to VX.
-                5 => {
-                    self.delay_timer = self.v[x];
-                    self.pc += 2;
-                }
+            // Selects the bitplanes drawn to, cleared and scrolled.
+            Instruction::Plane(n) => {
+                self.planes = n & 0x3;
+                self.pc = self.pc.wrapping_add(2);
+            }
 
-                // Sets the sound timer to VX.
-                8 => {
-                    self.sound_timer = self.v[x];
-                    self.pc += 2;
+            // Loads the 16 byte audio pattern from memory at I.
+            Instruction::Audio => {
+                self.check_range(self.i, 16)?;
+                for n in 0..16 {
+                    self.audio[n] = self.read(self.i, n)?;
                 }
+                self.audio_loaded = true;
+                self.pc = self.pc.wrapping_add(2);
+            }
 
-                // Adds VX to I. VF is not affected.
-                14 => {
-                    self.i += self.v[x] as u16;
-                    self.pc += 2;
-                }
+            // Sets the audio pattern playback pitch.
+            Instruction::Pitch(x) => {
+                self.pitch = self.v[x];
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            // Skips the next instruction if the key stored in VX is pressed.
+            Instruction::Skp(x) => self.skip_if(self.is_key_pressed(self.v[x]))?,
 
-                _ => panic!("ERROR OP_F1 NOT RECOGNIZED"),
-            },
+            // Skips the next instruction if the key stored in VX isn't pressed.
+            Instruction::Sknp(x) => self.skip_if(!self.is_key_pressed(self.v[x]))?,
+
+            // Sets VX to the value of the delay timer.
+            Instruction::LdVxDt(x) => {
+                self.v[x] = self.delay_timer;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            // A key press is awaited, and then stored in VX.
+            Instruction::LdVxK(x) => {
+                self.wait_key = true;
+                self.wait_reg = x;
+                self.wait_held = None;
+            }
+
+            // Sets the delay timer to VX.
+            Instruction::LdDtVx(x) => {
+                self.delay_timer = self.v[x];
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            // Sets the sound timer to VX.
+            Instruction::LdStVx(x) => {
+                self.sound_timer = self.v[x];
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            // Adds VX to I. VF is not affected.
+            Instruction::AddIVx(x) => {
+                self.i = self.i.wrapping_add(self.v[x] as u16);
+                self.pc = self.pc.wrapping_add(2);
+            }
 
             // Sets I to the location of the sprite for the character in VX.
-            2 => {
+            Instruction::LdFVx(x) => {
                 self.i = (self.v[x] as u16) * 5;
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            // Sets I to the location of the big sprite for the character in VX.
+            Instruction::LdHfVx(x) => {
+                self.i = BIG_FONT_ADDR + (self.v[x] as u16 & 0xF) * 10;
+                self.pc = self.pc.wrapping_add(2);
             }
 
             // Stores the binary-coded decimal representation of VX.
-            3 => {
-                self.memory[self.i as usize] = self.v[x] / 100;
-                self.memory[self.i as usize + 1] = (self.v[x] % 100) / 10;
-                self.memory[self.i as usize + 2] = self.v[x] % 10;
-                self.pc += 2;
+            Instruction::LdBVx(x) => {
+                self.check_range(self.i, 3)?;
+                self.write(self.i, 0, self.v[x] / 100)?;
+                self.write(self.i, 1, (self.v[x] % 100) / 10)?;
+                self.write(self.i, 2, self.v[x] % 10)?;
+                self.pc = self.pc.wrapping_add(2);
             }
 
             // Stores V0 to VX (including VX) in memory starting at address I.
-            5 => {
+            Instruction::LdIVx(x) => {
+                self.check_range(self.i, x + 1)?;
                 for i in 0..x + 1 {
-                    self.memory[self.i as usize + i] = self.v[i];
+                    self.write(self.i, i, self.v[i])?;
                 }
-                self.pc += 2;
+                self.increment_i_after_load_store(x);
+                self.pc = self.pc.wrapping_add(2);
             }
 
             // Fills V0 to VX (including VX) with values from memory starting at address I.
-            6 => {
+            Instruction::LdVxI(x) => {
+                self.check_range(self.i, x + 1)?;
                 for i in 0..x + 1 {
-                    self.v[i] = self.memory[self.i as usize + i];
+                    self.v[i] = self.read(self.i, i)?;
+                }
+                self.increment_i_after_load_store(x);
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            // Stores V0 to VX (including VX) in the RPL user flags.
+            Instruction::LdRVx(x) => {
+                self.rpl[..=x].copy_from_slice(&self.v[..=x]);
+                self.pc = self.pc.wrapping_add(2);
+            }
+
+            // Fills V0 to VX (including VX) from the RPL user flags.
+            Instruction::LdVxR(x) => {
+                self.v[..=x].copy_from_slice(&self.rpl[..=x]);
+                self.pc = self.pc.wrapping_add(2);
+            }
+        }
+        Ok(())
+    }
+
+    // FX55 and FX65 may leave I after the registers, depending on quirks.
+    fn increment_i_after_load_store(&mut self, x: usize) {
+        match self.quirks.load_store {
+            LoadStore::Unchanged => {}
+            LoadStore::IncrementX => self.i = self.i.wrapping_add(x as u16),
+            LoadStore::IncrementXPlusOne => self.i = self.i.wrapping_add(x as u16 + 1),
+        }
+    }
+
+    // Clears the selected planes.
+    fn clear_screen(&mut self) {
+        for row in self.gfx.iter_mut() {
+            for px in row.iter_mut() {
+                *px &= !self.planes;
+            }
+        }
+    }
+
+    // Moves the selected planes by (dx, dy), filling in with blank pixels.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (w, h) = (self.width() as isize, self.height() as isize);
+        let old = self.gfx;
+        for y in 0..h {
+            for x in 0..w {
+                let (sx, sy) = (x - dx, y - dy);
+                let moved = if sx >= 0 && sx < w && sy >= 0 && sy < h {
+                    old[sy as usize][sx as usize]
+                } else {
+                    0
+                };
+                let px = &mut self.gfx[y as usize][x as usize];
+                *px = (*px & !self.planes) | (moved & self.planes);
+            }
+        }
+    }
+
+    // XORs a `width` (8 or 16) by `height` sprite from I onto the screen at
+    // (x, y), wrapping around the edges or clipped at them depending on
+    // quirks. VF is set if any pixel was erased.
+    //
+    // With both XO-CHIP planes selected the sprite data for plane 2 follows
+    // the one for plane 1.
+    fn draw_sprite(&mut self, x: u8, y: u8, width: usize, height: usize) -> Result<(), Fault> {
+        let (w, h) = (self.width(), self.height());
+        let bytes_per_row = width / 8;
+        let planes = self.planes;
+        let sprite_len = planes.count_ones() as usize * height * bytes_per_row;
+        self.check_range(self.i, sprite_len)?;
+        let mut offset = 0;
+        let (x, y) = (x as usize % w, y as usize % h);
+        let clip = self.quirks.clip_sprites;
+        self.v[0xf] = 0;
+
+        for plane in [1u8, 2].iter().filter(|&&p| planes & p != 0) {
+            for row in 0..height {
+                if clip && y + row >= h {
+                    break;
+                }
+                let py = (y + row) % h;
+                for b in 0..width {
+                    if clip && x + b >= w {
+                        break;
+                    }
+                    let px = (x + b) % w;
+                    let byte = self.read(self.i, offset + row * bytes_per_row + b / 8)?;
+                    let bit = (byte >> (7 - b % 8)) & 1;
+                    let color: u8 = bit * plane;
+                    if color & self.gfx[py][px] != 0 {
+                        self.v[0x0f] = 1;
+                    }
+                    self.gfx[py][px] ^= color;
+                }
+            }
+            offset += height * bytes_per_row;
+        }
+        Ok(())
+    }
+
+    // The 8XYN arithmetic and logic group.
+    fn alu(&mut self, ins: Instruction) {
+        match ins {
+            // Sets VX to the value of VY.
+            Instruction::LdReg { x, y } => self.v[x] = self.v[y],
+
+            // Sets VX to VX or VY. (Bitwise OR operation)
+            // With the VF reset quirk these three also clear VF.
+            Instruction::Or { x, y } => {
+                self.v[x] |= self.v[y];
+                self.reset_vf_if_quirk();
+            }
+
+            // Sets VX to VX and VY. (Bitwise AND operation)
+            Instruction::And { x, y } => {
+                self.v[x] &= self.v[y];
+                self.reset_vf_if_quirk();
+            }
+
+            // Sets VX to VX xor VY.
+            Instruction::Xor { x, y } => {
+                self.v[x] ^= self.v[y];
+                self.reset_vf_if_quirk();
+            }
+
+            // Adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't.
+            Instruction::AddReg { x, y } => {
+                let res = self.v[x] as u16 + self.v[y] as u16;
+                if res > 0xFF {
+                    self.v[0xf] = 1;
+                } else {
+                    self.v[0xf] = 0;
+                }
+                self.v[x] = res as u8;
+            }
+
+            // VY is subtracted from VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
+            Instruction::Sub { x, y } => {
+                let res = self.v[x] as i16 - self.v[y] as i16;
+                if res < 0x0 {
+                    self.v[0xf] = 0;
+                } else {
+                    self.v[0xf] = 1;
+                }
+                self.v[x] = self.v[x].wrapping_sub(self.v[y]);
+            }
+
+            // Stores the least significant bit of VX in VF and then shifts VX to the right by 1.
+            // With the shift quirk VY is shifted into VX instead.
+            Instruction::Shr { x, y } => {
+                let src = if self.quirks.shift_vy {
+                    self.v[y]
+                } else {
+                    self.v[x]
+                };
+                self.v[x] = src >> 1;
+                self.v[0xf] = src & 0b00000001;
+            }
+
+            // Sets VX to VY minus VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
+            Instruction::Subn { x, y } => {
+                let res = self.v[y] as i16 - self.v[x] as i16;
+                if res < 0x0 {
+                    self.v[0xf] = 0;
+                } else {
+                    self.v[0xf] = 1;
                 }
-                self.pc += 2;
+                self.v[x] = self.v[y].wrapping_sub(self.v[x]);
             }
 
-            _ => panic!("ERROR OP_F NOT RECOGNIZED"),
+            // Stores the most significant bit of VX in VF and then shifts VX to the left by 1.
+            // With the shift quirk VY is shifted into VX instead.
+            Instruction::Shl { x, y } => {
+                let src = if self.quirks.shift_vy {
+                    self.v[y]
+                } else {
+                    self.v[x]
+                };
+                self.v[x] = src << 1;
+                self.v[0xf] = (src & 0b10000000) >> 7;
+            }
+
+            _ => unreachable!("{:?} is not an 8XYN instruction", ins),
+        }
+    }
+
+    fn reset_vf_if_quirk(&mut self) {
+        if self.quirks.vf_reset {
+            self.v[0xf] = 0;
+        }
+    }
+
+    // Moves to the next instruction, skipping one more if `cond` holds.
+    // On XO-CHIP the skipped instruction may be the 4 byte F000 NNNN.
+    fn skip_if(&mut self, cond: bool) -> Result<(), Fault> {
+        let mut step = 2;
+        if cond {
+            step += 2;
+            // The look-ahead is a read like any other, under the memory policy
+            if self.platform == Platform::XoChip && self.fetch(self.pc.wrapping_add(2))? == 0xF000 {
+                step += 2;
+            }
         }
+        self.pc = self.pc.wrapping_add(step);
+        Ok(())
     }
 }
 
-fn write_to_file(s: &str) {
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open("instructions.txt")
-        .unwrap();
+// Registers X to Y, counting down if X is the larger one.
+fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+    (0..=x.abs_diff(y)).map(move |n| if x <= y { x + n } else { x - n })
+}
+
+/// An error raised while executing a ROM.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The word at `pc` doesn't decode to any instruction.
+    InvalidOpcode { pc: u16, opcode: u16 },
+    /// The call at `pc` would nest deeper than the stack allows.
+    StackOverflow { pc: u16 },
+    /// The `00EE` at `pc` returns with no call to return from.
+    StackUnderflow { pc: u16 },
+    /// The instruction at `pc` touched `addr`, past the end of memory, under
+    /// [`MemoryPolicy::Fault`].
+    MemoryOutOfRange { pc: u16, addr: u32 },
+}
 
-    if let Err(e) = writeln!(file, "{}", s) {
-        eprintln!("Couldn't write to file: {}", e);
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid opcode {:#06x} at {:#05x}", opcode, pc)
+            }
+            Fault::StackOverflow { pc } => write!(f, "stack overflow at {:#05x}", pc),
+            Fault::StackUnderflow { pc } => {
+                write!(f, "return with an empty stack at {:#05x}", pc)
+            }
+            Fault::MemoryOutOfRange { pc, addr } => {
+                write!(
+                    f,
+                    "memory access at {:#x} out of range at {:#05x}",
+                    addr, pc
+                )
+            }
+        }
     }
 }
 
+impl error::Error for Fault {}
+
 #[cfg(test)]
 #[path = "./cpu_tests.rs"]
 mod cpu_tests;
@@ -1,8 +1,9 @@
 use super::*;
+use crate::instruction::decode;
 
 #[test]
 fn test_init() {
-    let cpu = Cpu::initialize(&vec![1, 2, 3]);
+    let cpu = Cpu::initialize(&[1, 2, 3], Quirks::default());
     assert_eq!(cpu.opcode, 0);
     assert_eq!(cpu.memory[0x200], 1);
     assert_eq!(cpu.memory[0x201], 2);
@@ -15,10 +16,10 @@ fn test_init() {
 
 #[test]
 fn op_00e0() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
-    cpu.emulate_instruction(0x00e0, 0);
-    for i in 0..(display::HEIGHT) as usize {
-        for j in 0..(display::WIDTH) as usize {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.emulate_instruction(0x00e0).unwrap();
+    for i in 0..(HEIGHT) as usize {
+        for j in 0..(WIDTH) as usize {
             assert_eq!(cpu.gfx[i][j], 0);
         }
     }
@@ -28,77 +29,120 @@ fn op_00e0() {
 
 #[test]
 fn op_00ee() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.sp = 3;
     cpu.stack[3] = 0x2222;
-    cpu.emulate_instruction(0x00ee, 0);
+    cpu.emulate_instruction(0x00ee).unwrap();
     assert_eq!(cpu.pc, cpu.stack[cpu.sp as usize]);
     assert_eq!(cpu.sp, 2);
     assert_eq!(cpu.v[0xf], 0);
 }
 
+#[test]
+fn op_00ee_underflow() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    assert_eq!(
+        cpu.emulate_instruction(0x00ee),
+        Err(Fault::StackUnderflow { pc: 0x200 })
+    );
+    assert_eq!(cpu.pc, 0x200);
+    assert_eq!(cpu.sp, 0);
+}
+
 #[test]
 fn op_1nnn() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
-    cpu.emulate_instruction(0x1234, 0);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.emulate_instruction(0x1234).unwrap();
     assert_eq!(cpu.pc, 0x0234);
     assert_eq!(cpu.v[0xf], 0);
 }
 
 #[test]
 fn op_2nnn() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
-    cpu.emulate_instruction(0x2345, 0);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.emulate_instruction(0x2345).unwrap();
     assert_eq!(cpu.stack[0], 0x202);
     assert_eq!(cpu.pc, 0x0345);
     assert_eq!(cpu.sp, 1);
     assert_eq!(cpu.v[0xf], 0);
 }
 
+#[test]
+fn op_2nnn_end_of_memory() {
+    // A call from the last XO-CHIP word returns to address 0
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_platform(Platform::XoChip);
+    cpu.pc = 0xfffe;
+    cpu.emulate_instruction(0x2345).unwrap();
+    assert_eq!(cpu.stack[0], 0x0000);
+    assert_eq!(cpu.pc, 0x0345);
+}
+
+#[test]
+fn op_2nnn_overflow() {
+    // 2200: calls itself forever
+    let mut cpu = Cpu::initialize(&[0x22, 0x00], Quirks::default());
+    for _ in 0..STACK_DEPTH {
+        cpu.emulate_cycle().unwrap();
+    }
+    assert_eq!(cpu.sp as usize, STACK_DEPTH);
+    assert_eq!(cpu.emulate_cycle(), Err(Fault::StackOverflow { pc: 0x200 }));
+    assert_eq!(cpu.sp as usize, STACK_DEPTH);
+
+    cpu.set_stack_depth(1000);
+    assert_eq!(cpu.stack_depth(), MAX_STACK_DEPTH);
+    for _ in STACK_DEPTH..MAX_STACK_DEPTH {
+        cpu.emulate_cycle().unwrap();
+    }
+    assert!(cpu.emulate_cycle().is_err());
+    cpu.set_stack_depth(0);
+    assert_eq!(cpu.stack_depth(), 1);
+}
+
 #[test]
 fn op_3xnn() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[1] = 1;
-    cpu.emulate_instruction(0x3101, 0);
+    cpu.emulate_instruction(0x3101).unwrap();
     assert_eq!(cpu.pc, 0x204);
     assert_eq!(cpu.v[0xf], 0);
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
-    cpu.emulate_instruction(0x3101, 0);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.emulate_instruction(0x3101).unwrap();
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
 }
 
 #[test]
 fn op_4xnn() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[1] = 1;
-    cpu.emulate_instruction(0x4101, 0);
+    cpu.emulate_instruction(0x4101).unwrap();
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
-    cpu.emulate_instruction(0x4101, 0);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.emulate_instruction(0x4101).unwrap();
     assert_eq!(cpu.pc, 0x204);
     assert_eq!(cpu.v[0xf], 0);
 }
 
 #[test]
 fn op_5xy0() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[2] = 3;
     cpu.v[1] = 1;
-    cpu.emulate_instruction(0x5210, 0);
+    cpu.emulate_instruction(0x5210).unwrap();
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
-    cpu.emulate_instruction(0x5100, 0);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.emulate_instruction(0x5100).unwrap();
     assert_eq!(cpu.pc, 0x204);
     assert_eq!(cpu.v[0xf], 0);
 }
 
 #[test]
 fn op_6xn0() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
-    cpu.emulate_instruction(0x6201, 0);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.emulate_instruction(0x6201).unwrap();
     assert_eq!(cpu.v[2], 1);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
@@ -106,8 +150,8 @@ fn op_6xn0() {
 
 #[test]
 fn op_7xnn() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
-    cpu.emulate_instruction(0x7344, 0);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.emulate_instruction(0x7344).unwrap();
     assert_eq!(cpu.v[3], 0x44);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
@@ -115,9 +159,9 @@ fn op_7xnn() {
 
 #[test]
 fn op_8xy0() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[4] = 3;
-    cpu.emulate_instruction(0x8340, 0);
+    cpu.emulate_instruction(0x8340).unwrap();
     assert_eq!(cpu.v[3], 0x3);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
@@ -125,10 +169,10 @@ fn op_8xy0() {
 
 #[test]
 fn op_8xy1() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[3] = 0b00000100;
     cpu.v[4] = 0b00000001;
-    cpu.emulate_instruction(0x8341, 0);
+    cpu.emulate_instruction(0x8341).unwrap();
     assert_eq!(cpu.v[3], 0b00000101);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
@@ -136,10 +180,10 @@ fn op_8xy1() {
 
 #[test]
 fn op_8xy2() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[3] = 0b00000100;
     cpu.v[4] = 0b00000101;
-    cpu.emulate_instruction(0x8342, 0);
+    cpu.emulate_instruction(0x8342).unwrap();
     assert_eq!(cpu.v[3], 0b00000100);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
@@ -147,10 +191,10 @@ fn op_8xy2() {
 
 #[test]
 fn op_8xy3() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[3] = 0b00000100;
     cpu.v[4] = 0b00000101;
-    cpu.emulate_instruction(0x8343, 0);
+    cpu.emulate_instruction(0x8343).unwrap();
     assert_eq!(cpu.v[3], 0b00000001);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
@@ -158,17 +202,17 @@ fn op_8xy3() {
 
 #[test]
 fn op_8xy4() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[3] = 0x12;
     cpu.v[4] = 0x5;
-    cpu.emulate_instruction(0x8344, 0);
+    cpu.emulate_instruction(0x8344).unwrap();
     assert_eq!(cpu.v[3], 0x17);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[3] = 0xff;
     cpu.v[4] = 0x5;
-    cpu.emulate_instruction(0x8344, 0);
+    cpu.emulate_instruction(0x8344).unwrap();
     assert_eq!(cpu.v[3], 0x4);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 1);
@@ -176,17 +220,17 @@ fn op_8xy4() {
 
 #[test]
 fn op_8xy5() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[3] = 0x12;
     cpu.v[4] = 0x5;
-    cpu.emulate_instruction(0x8345, 0);
+    cpu.emulate_instruction(0x8345).unwrap();
     assert_eq!(cpu.v[3], 0xd);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 1);
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[3] = 0x2;
     cpu.v[4] = 0x3;
-    cpu.emulate_instruction(0x8345, 0);
+    cpu.emulate_instruction(0x8345).unwrap();
     assert_eq!(cpu.v[3], 0xff);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
@@ -194,15 +238,15 @@ fn op_8xy5() {
 
 #[test]
 fn op_8x06() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[6] = 0b00000101;
-    cpu.emulate_instruction(0x8606, 0);
+    cpu.emulate_instruction(0x8606).unwrap();
     assert_eq!(cpu.v[6], 0b00000010);
     assert_eq!(cpu.v[0xf], 1);
     assert_eq!(cpu.pc, 0x202);
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[6] = 0b00000100;
-    cpu.emulate_instruction(0x8606, 0);
+    cpu.emulate_instruction(0x8606).unwrap();
     assert_eq!(cpu.v[6], 0b00000010);
     assert_eq!(cpu.v[0xf], 0);
     assert_eq!(cpu.pc, 0x202);
@@ -210,17 +254,17 @@ fn op_8x06() {
 
 #[test]
 fn op_8xy7() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[3] = 0x5;
     cpu.v[4] = 0x12;
-    cpu.emulate_instruction(0x8347, 0);
+    cpu.emulate_instruction(0x8347).unwrap();
     assert_eq!(cpu.v[3], 0xd);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 1);
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[3] = 0x3;
     cpu.v[4] = 0x2;
-    cpu.emulate_instruction(0x8347, 0);
+    cpu.emulate_instruction(0x8347).unwrap();
     assert_eq!(cpu.v[3], 0xff);
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
@@ -228,15 +272,15 @@ fn op_8xy7() {
 
 #[test]
 fn op_8x0e() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[6] = 0b10000101;
-    cpu.emulate_instruction(0x860e, 0);
+    cpu.emulate_instruction(0x860e).unwrap();
     assert_eq!(cpu.v[6], 0b00001010);
     assert_eq!(cpu.v[0xf], 1);
     assert_eq!(cpu.pc, 0x202);
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[6] = 0b00000100;
-    cpu.emulate_instruction(0x860e, 0);
+    cpu.emulate_instruction(0x860e).unwrap();
     assert_eq!(cpu.v[6], 0b00001000);
     assert_eq!(cpu.v[0xf], 0);
     assert_eq!(cpu.pc, 0x202);
@@ -244,24 +288,24 @@ fn op_8x0e() {
 
 #[test]
 fn op_9xy0() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[3] = 1;
     cpu.v[4] = 5;
-    cpu.emulate_instruction(0x9340, 0);
+    cpu.emulate_instruction(0x9340).unwrap();
     assert_eq!(cpu.pc, 0x204);
     assert_eq!(cpu.v[0xf], 0);
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[3] = 1;
     cpu.v[4] = 1;
-    cpu.emulate_instruction(0x9340, 0);
+    cpu.emulate_instruction(0x9340).unwrap();
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.v[0xf], 0);
 }
 
 #[test]
 fn op_annn() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
-    cpu.emulate_instruction(0xa234, 0);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.emulate_instruction(0xa234).unwrap();
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.i, 0x234);
     assert_eq!(cpu.v[0xf], 0);
@@ -269,28 +313,28 @@ fn op_annn() {
 
 #[test]
 fn op_bnnn() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[0] = 0x4;
-    cpu.emulate_instruction(0xb234, 0);
+    cpu.emulate_instruction(0xb234).unwrap();
     assert_eq!(cpu.pc, 0x238);
     assert_eq!(cpu.v[0xf], 0);
 }
 
 #[test]
 fn op_cxnn() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[0] = 0x4;
-    cpu.emulate_instruction(0xc000, 0);
+    cpu.emulate_instruction(0xc000).unwrap();
     assert_eq!(cpu.v[0], 0);
     assert_eq!(cpu.v[0xf], 0);
-    cpu.emulate_instruction(0xc00f, 0);
+    cpu.emulate_instruction(0xc00f).unwrap();
     assert_eq!(cpu.v[0] & 0xf0, 0);
     assert_eq!(cpu.v[0xf], 0);
 }
 
 #[test]
 fn op_dxyn() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.i = 0;
     cpu.memory[0] = 0b11111111;
     cpu.memory[1] = 0b00000000;
@@ -299,7 +343,7 @@ fn op_dxyn() {
     cpu.gfx[1][0] = 1;
     cpu.gfx[1][1] = 0;
     cpu.v[0] = 0;
-    cpu.emulate_instruction(0xd002, 0);
+    cpu.emulate_instruction(0xd002).unwrap();
 
     assert_eq!(cpu.gfx[0][0], 0);
     assert_eq!(cpu.gfx[0][1], 1);
@@ -311,71 +355,109 @@ fn op_dxyn() {
 
 #[test]
 fn op_ex9e() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[4] = 0x4;
-    cpu.emulate_instruction(0xe49e, 6);
+    cpu.press_key(6);
+    cpu.emulate_instruction(0xe49e).unwrap();
     assert_eq!(cpu.pc, 0x202);
-    assert_eq!(cpu.wait_key, false);
+    assert!(!cpu.wait_key);
     assert_eq!(cpu.v[0xf], 0);
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[4] = 0x6;
-    cpu.emulate_instruction(0xe49e, 6);
+    cpu.press_key(6);
+    cpu.emulate_instruction(0xe49e).unwrap();
     assert_eq!(cpu.pc, 0x204);
-    assert_eq!(cpu.wait_key, false);
+    assert!(!cpu.wait_key);
     assert_eq!(cpu.v[0xf], 0);
 }
 
 #[test]
 fn op_exa1() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[2] = 0x4;
-    cpu.emulate_instruction(0xe2a1, 6);
+    cpu.press_key(6);
+    cpu.emulate_instruction(0xe2a1).unwrap();
     assert_eq!(cpu.pc, 0x204);
-    assert_eq!(cpu.wait_key, false);
+    assert!(!cpu.wait_key);
     assert_eq!(cpu.v[0xf], 0);
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[2] = 0x6;
-    cpu.emulate_instruction(0xe2a1, 6);
+    cpu.press_key(6);
+    cpu.emulate_instruction(0xe2a1).unwrap();
     assert_eq!(cpu.pc, 0x202);
-    assert_eq!(cpu.wait_key, false);
+    assert!(!cpu.wait_key);
     assert_eq!(cpu.v[0xf], 0);
 }
 
 #[test]
 fn op_fx07() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.delay_timer = 0x14;
-    cpu.emulate_instruction(0xf407, 0);
+    cpu.emulate_instruction(0xf407).unwrap();
     assert_eq!(cpu.pc, 0x202);
-    assert_eq!(cpu.v[4], 0x13);
+    assert_eq!(cpu.v[4], 0x14);
     assert_eq!(cpu.v[0xf], 0);
 }
 
 #[test]
 fn op_fx0a() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
-    cpu.emulate_instruction(0xf40a, 0);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.emulate_instruction(0xf40a).unwrap();
     assert_eq!(cpu.pc, 0x200);
-    assert_eq!(cpu.wait_key, true);
+    assert!(cpu.wait_key);
     assert_eq!(cpu.v[0xf], 0);
 
     // check that it does nothing if it doesn't receive a key
-    cpu.emulate_instruction(0x1fff, 0);
+    cpu.emulate_instruction(0x1fff).unwrap();
     assert_eq!(cpu.pc, 0x200);
-    assert_eq!(cpu.wait_key, true);
+    assert!(cpu.wait_key);
 
-    cpu.emulate_instruction(0xf40a, 3);
+    // a pressed key is only stored once it is released again
+    cpu.press_key(3);
+    cpu.emulate_cycle().unwrap();
+    assert_eq!(cpu.pc, 0x200);
+    assert!(cpu.wait_key);
+    assert_eq!(cpu.v[4], 0);
+
+    cpu.release_key(3);
+    cpu.emulate_cycle().unwrap();
     assert_eq!(cpu.pc, 0x202);
-    assert_eq!(cpu.wait_key, false);
+    assert!(!cpu.wait_key);
     assert_eq!(cpu.v[4], 3);
     assert_eq!(cpu.v[0xf], 0);
 }
 
+#[test]
+fn op_fx0a_key_0() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.v[2] = 0xff;
+    cpu.emulate_instruction(0xf20a).unwrap();
+    cpu.press_key(0);
+    cpu.emulate_cycle().unwrap();
+    cpu.release_key(0);
+    cpu.emulate_cycle().unwrap();
+    assert_eq!(cpu.v[2], 0);
+    assert_eq!(cpu.pc, 0x202);
+}
+
+#[test]
+fn test_keypad() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.press_key(0xa);
+    cpu.press_key(0xf);
+    assert!(cpu.is_key_pressed(0xa));
+    assert!(cpu.is_key_pressed(0xf));
+    assert!(!cpu.is_key_pressed(0x0));
+    cpu.release_key(0xa);
+    assert!(!cpu.is_key_pressed(0xa));
+    assert!(cpu.is_key_pressed(0xf));
+}
+
 #[test]
 fn op_fx15() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[4] = 20;
-    cpu.emulate_instruction(0xf415, 0);
+    cpu.emulate_instruction(0xf415).unwrap();
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.delay_timer, 20);
     assert_eq!(cpu.v[0xf], 0);
@@ -383,9 +465,9 @@ fn op_fx15() {
 
 #[test]
 fn op_fx18() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[4] = 20;
-    cpu.emulate_instruction(0xf418, 0);
+    cpu.emulate_instruction(0xf418).unwrap();
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.sound_timer, 20);
     assert_eq!(cpu.v[0xf], 0);
@@ -393,10 +475,10 @@ fn op_fx18() {
 
 #[test]
 fn op_fx1e() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[4] = 20;
     cpu.i = 4;
-    cpu.emulate_instruction(0xf41e, 0);
+    cpu.emulate_instruction(0xf41e).unwrap();
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.i, 24);
     assert_eq!(cpu.v[0xf], 0);
@@ -404,9 +486,9 @@ fn op_fx1e() {
 
 #[test]
 fn op_fx29() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[4] = 2;
-    cpu.emulate_instruction(0xf429, 0);
+    cpu.emulate_instruction(0xf429).unwrap();
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.i, 5 * 2);
     assert_eq!(cpu.v[0xf], 0);
@@ -414,10 +496,10 @@ fn op_fx29() {
 
 #[test]
 fn op_fx33() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.v[5] = 123;
     cpu.i = 1000;
-    cpu.emulate_instruction(0xf533, 0);
+    cpu.emulate_instruction(0xf533).unwrap();
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.i, 1000);
     assert_eq!(cpu.memory[1000], 1);
@@ -428,25 +510,25 @@ fn op_fx33() {
 
 #[test]
 fn op_fx55() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.i = 1000;
-    cpu.emulate_instruction(0xf553, 0);
+    cpu.emulate_instruction(0xf555).unwrap();
     assert_eq!(cpu.pc, 0x202);
     assert_eq!(cpu.i, 1000);
     for i in 0..16 {
-        assert_eq!(cpu.memory[1000 + i as usize], cpu.v[i]);
+        assert_eq!(cpu.memory[1000 + i], cpu.v[i]);
     }
     assert_eq!(cpu.v[0xf], 0);
 }
 
 #[test]
 fn op_fx65() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     for i in 0..16 {
         cpu.memory[1000 + i as usize] = i as u8;
     }
     cpu.i = 1000;
-    cpu.emulate_instruction(0xff65, 0);
+    cpu.emulate_instruction(0xff65).unwrap();
     assert_eq!(cpu.i, 1000);
     for i in 0..16 {
         assert_eq!(cpu.v[i as usize], cpu.memory[1000 + i as usize]);
@@ -456,10 +538,488 @@ fn op_fx65() {
 
 #[test]
 fn test_timers() {
-    let mut cpu = Cpu::initialize(&vec![1, 1, 1]);
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
     cpu.delay_timer = 200;
     cpu.sound_timer = 100;
-    cpu.emulate_instruction(0x1234, 0);
+    cpu.emulate_instruction(0x1234).unwrap();
+    assert_eq!(cpu.delay_timer, 200);
+    assert_eq!(cpu.sound_timer, 100);
+    cpu.tick_timers();
     assert_eq!(cpu.delay_timer, 199);
     assert_eq!(cpu.sound_timer, 99);
 }
+
+#[test]
+fn test_short_beep() {
+    // 0x200: LD V0, 1 / LD ST, V0 / JP 0x204
+    let mut cpu = Cpu::initialize(&[0x60, 0x01, 0xF0, 0x18, 0x12, 0x04], Quirks::default());
+    assert!(!cpu.sound_active());
+    cpu.run_frame().unwrap();
+    // The timer ran out at the end of the frame, but the buzzer sounded in it
+    assert_eq!(cpu.sound_timer, 0);
+    assert!(cpu.sound_active());
+    cpu.run_frame().unwrap();
+    assert!(!cpu.sound_active());
+}
+
+#[test]
+fn test_run_frame() {
+    // 0x200: ADD V0, 1 / JP 0x200
+    let mut cpu = Cpu::initialize(&[0x70, 0x01, 0x12, 0x00], Quirks::default());
+    cpu.delay_timer = 10;
+    cpu.run_frame().unwrap();
+    // 700 Hz is 11 instructions and 40/60 left over
+    assert_eq!(cpu.v[0], 6);
+    assert_eq!(cpu.delay_timer, 9);
+
+    let mut cpu = Cpu::initialize(&[0x70, 0x01, 0x12, 0x00], Quirks::default());
+    cpu.set_clock_hz(90);
+    assert_eq!(cpu.clock_hz(), 90);
+    for _ in 0..TIMER_HZ {
+        cpu.run_frame().unwrap();
+    }
+    assert_eq!(cpu.v[0], 45);
+    assert_eq!(cpu.delay_timer, 0);
+}
+
+#[test]
+fn test_invalid_opcode() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.delay_timer = 10;
+    let res = cpu.emulate_instruction(0x8008);
+    assert_eq!(
+        res,
+        Err(Fault::InvalidOpcode {
+            pc: 0x200,
+            opcode: 0x8008
+        })
+    );
+    assert_eq!(cpu.pc, 0x200);
+    assert_eq!(cpu.delay_timer, 10);
+}
+
+#[test]
+fn test_decode() {
+    assert_eq!(decode(0x00e0), Ok(Instruction::Cls));
+    assert_eq!(decode(0x1234), Ok(Instruction::Jp(0x234)));
+    assert_eq!(decode(0xd125), Ok(Instruction::Drw { x: 1, y: 2, n: 5 }));
+    assert_eq!(decode(0x8abe), Ok(Instruction::Shl { x: 0xa, y: 0xb }));
+    assert_eq!(decode(0xf365), Ok(Instruction::LdVxI(3)));
+    assert_eq!(decode(0x0123).unwrap_err().opcode, 0x0123);
+    assert!(decode(0x5121).is_err());
+    assert!(decode(0xe1ff).is_err());
+}
+
+#[test]
+fn test_schip_needs_platform() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    assert_eq!(
+        cpu.emulate_instruction(0x00ff),
+        Err(Fault::InvalidOpcode {
+            pc: 0x200,
+            opcode: 0x00ff
+        })
+    );
+    cpu.set_platform(Platform::SuperChip);
+    cpu.emulate_instruction(0x00ff).unwrap();
+    assert_eq!(cpu.pc, 0x202);
+}
+
+#[test]
+fn op_00fe_00ff() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_platform(Platform::SuperChip);
+    cpu.gfx[0][0] = 1;
+    cpu.emulate_instruction(0x00ff).unwrap();
+    assert_eq!((cpu.width(), cpu.height()), (128, 64));
+    assert_eq!(cpu.gfx[0][0], 0);
+    cpu.emulate_instruction(0x00fe).unwrap();
+    assert_eq!((cpu.width(), cpu.height()), (64, 32));
+    assert_eq!(cpu.pc, 0x204);
+}
+
+#[test]
+fn op_00cn() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_platform(Platform::SuperChip);
+    cpu.gfx[0][5] = 1;
+    cpu.gfx[31][5] = 1;
+    cpu.emulate_instruction(0x00c3).unwrap();
+    assert_eq!(cpu.gfx[0][5], 0);
+    assert_eq!(cpu.gfx[3][5], 1);
+    assert_eq!(cpu.gfx[31][5], 0);
+    assert_eq!(cpu.pc, 0x202);
+}
+
+#[test]
+fn op_00fb_00fc() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_platform(Platform::SuperChip);
+    cpu.gfx[2][0] = 1;
+    cpu.gfx[2][63] = 1;
+    cpu.emulate_instruction(0x00fb).unwrap();
+    assert_eq!(cpu.gfx[2][0], 0);
+    assert_eq!(cpu.gfx[2][4], 1);
+    assert_eq!(cpu.gfx[2][63], 0);
+    assert_eq!(cpu.gfx[2][67], 0);
+    cpu.emulate_instruction(0x00fc).unwrap();
+    assert_eq!(cpu.gfx[2][0], 1);
+    assert_eq!(cpu.gfx[2][4], 0);
+    cpu.emulate_instruction(0x00fc).unwrap();
+    assert_eq!(cpu.gfx[2][0], 0);
+    assert_eq!(cpu.pc, 0x206);
+}
+
+#[test]
+fn op_00fd() {
+    // 0x200: EXIT / JP 0x200
+    let mut cpu = Cpu::initialize(&[0x00, 0xfd, 0x12, 0x00], Quirks::default());
+    cpu.set_platform(Platform::SuperChip);
+    cpu.run_frame().unwrap();
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.pc, 0x200);
+}
+
+#[test]
+fn op_dxy0() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_platform(Platform::SuperChip);
+    cpu.emulate_instruction(0x00ff).unwrap();
+    cpu.i = 1000;
+    for row in 0..16 {
+        cpu.memory[1000 + row * 2] = 0b10000000;
+        cpu.memory[1000 + row * 2 + 1] = 0b00000001;
+    }
+    cpu.v[0] = 120;
+    cpu.v[1] = 60;
+    cpu.emulate_instruction(0xd010).unwrap();
+    // wraps around the 128x64 screen
+    assert_eq!(cpu.gfx[60][120], 1);
+    assert_eq!(cpu.gfx[60][7], 1);
+    assert_eq!(cpu.gfx[11][120], 1);
+    assert_eq!(cpu.gfx[11][7], 1);
+    assert_eq!(cpu.gfx[60][121], 0);
+    assert_eq!(cpu.v[0xf], 0);
+    cpu.emulate_instruction(0xd010).unwrap();
+    assert_eq!(cpu.gfx[60][120], 0);
+    assert_eq!(cpu.v[0xf], 1);
+}
+
+#[test]
+fn op_fx30() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_platform(Platform::SuperChip);
+    cpu.v[2] = 3;
+    cpu.emulate_instruction(0xf230).unwrap();
+    assert_eq!(cpu.i, BIG_FONT_ADDR + 30);
+    assert_eq!(cpu.memory[cpu.i as usize], BIG_FONT_SET[30]);
+    assert_eq!(cpu.pc, 0x202);
+}
+
+#[test]
+fn op_fx75_fx85() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_platform(Platform::SuperChip);
+    for i in 0..8 {
+        cpu.v[i] = i as u8 + 1;
+    }
+    cpu.emulate_instruction(0xf775).unwrap();
+    cpu.v = [0; 16];
+    cpu.emulate_instruction(0xf385).unwrap();
+    assert_eq!(cpu.v[..5], [1, 2, 3, 4, 0]);
+    assert_eq!(cpu.pc, 0x204);
+}
+
+#[test]
+fn test_xochip_memory() {
+    let mut rom = vec![0; 0x2000];
+    rom[0x1fff] = 0xab;
+    let mut cpu = Cpu::initialize(&rom, Quirks::default());
+    cpu.set_platform(Platform::XoChip);
+    assert_eq!(cpu.memory[0x21ff], 0xab);
+    assert_eq!(Platform::XoChip.memory_size(), 0x10000);
+    assert_eq!(Platform::SuperChip.memory_size(), 0x1000);
+}
+
+#[test]
+fn op_f000_nnnn() {
+    let mut cpu = Cpu::initialize(&[0xf0, 0x00, 0xbe, 0xef], Quirks::default());
+    assert!(cpu.emulate_cycle().is_err());
+    cpu.set_platform(Platform::XoChip);
+    cpu.emulate_cycle().unwrap();
+    assert_eq!(cpu.i, 0xbeef);
+    assert_eq!(cpu.pc, 0x204);
+}
+
+#[test]
+fn test_xochip_skip_long() {
+    // 0x200: SE V0, 0 / LD I, 0x1234 / ...
+    let mut cpu = Cpu::initialize(&[0x30, 0x00, 0xf0, 0x00, 0x12, 0x34], Quirks::default());
+    cpu.set_platform(Platform::XoChip);
+    cpu.emulate_cycle().unwrap();
+    assert_eq!(cpu.pc, 0x206);
+}
+
+#[test]
+fn test_xochip_end_of_memory() {
+    // The program counter wraps from the last word back to 0
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_platform(Platform::XoChip);
+    cpu.pc = 0xfffe;
+    cpu.emulate_instruction(0x6001).unwrap();
+    assert_eq!(cpu.v[0], 1);
+    assert_eq!(cpu.pc, 0x0000);
+
+    // A skip over the last word, and over a long F000 NNNN there
+    cpu.pc = 0xfffc;
+    cpu.emulate_instruction(0x3001).unwrap();
+    assert_eq!(cpu.pc, 0x0000);
+    cpu.memory[0xfffe] = 0xf0;
+    cpu.memory[0xffff] = 0x00;
+    cpu.pc = 0xfffc;
+    cpu.emulate_instruction(0x3001).unwrap();
+    assert_eq!(cpu.pc, 0x0002);
+}
+
+#[test]
+fn op_5xy2_5xy3() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_platform(Platform::XoChip);
+    cpu.v[2] = 7;
+    cpu.v[3] = 8;
+    cpu.v[4] = 9;
+    cpu.i = 1000;
+    cpu.emulate_instruction(0x5242).unwrap();
+    assert_eq!(cpu.memory[1000..1003], [7, 8, 9]);
+    assert_eq!(cpu.i, 1000);
+    // loading in reverse order
+    cpu.emulate_instruction(0x5a83).unwrap();
+    assert_eq!(cpu.v[0xa], 7);
+    assert_eq!(cpu.v[0x9], 8);
+    assert_eq!(cpu.v[0x8], 9);
+    assert_eq!(cpu.pc, 0x204);
+}
+
+#[test]
+fn op_fn01_planes() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_platform(Platform::XoChip);
+    cpu.i = 1000;
+    cpu.memory[1000] = 0b10000000;
+    cpu.memory[1001] = 0b11000000;
+    cpu.emulate_instruction(0xf301).unwrap();
+    cpu.emulate_instruction(0xd001).unwrap();
+    assert_eq!(cpu.gfx[0][0], 3);
+    assert_eq!(cpu.gfx[0][1], 2);
+
+    // clearing and scrolling only touch the selected planes
+    cpu.emulate_instruction(0xf201).unwrap();
+    cpu.emulate_instruction(0x00fb).unwrap();
+    assert_eq!(cpu.gfx[0][0], 1);
+    assert_eq!(cpu.gfx[0][4], 2);
+    cpu.emulate_instruction(0x00e0).unwrap();
+    assert_eq!(cpu.gfx[0][0], 1);
+    assert_eq!(cpu.gfx[0][4], 0);
+}
+
+#[test]
+fn op_f002_fx3a() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_platform(Platform::XoChip);
+    assert_eq!(cpu.audio_pattern(), None);
+    cpu.i = 1000;
+    for n in 0..16 {
+        cpu.memory[1000 + n] = n as u8;
+    }
+    cpu.v[1] = 112;
+    cpu.emulate_instruction(0xf002).unwrap();
+    cpu.emulate_instruction(0xf13a).unwrap();
+    let p = cpu.audio_pattern().unwrap();
+    assert_eq!(p.bits[15], 15);
+    assert_eq!(p.pitch, 112);
+    assert_eq!(p.rate(), 8000.0);
+}
+
+#[test]
+fn test_quirk_shift_vy() {
+    let quirks = Quirks {
+        shift_vy: true,
+        ..Quirks::default()
+    };
+    let mut cpu = Cpu::initialize(&[1, 1, 1], quirks);
+    cpu.v[1] = 0b00000011;
+    cpu.v[2] = 0b10000001;
+    cpu.emulate_instruction(0x8126).unwrap();
+    assert_eq!(cpu.v[1], 0b01000000);
+    assert_eq!(cpu.v[0xf], 1);
+    cpu.emulate_instruction(0x812e).unwrap();
+    assert_eq!(cpu.v[1], 0b00000010);
+    assert_eq!(cpu.v[0xf], 1);
+    assert_eq!(cpu.v[2], 0b10000001);
+}
+
+#[test]
+fn test_quirk_load_store() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::cosmac_vip());
+    cpu.i = 1000;
+    cpu.emulate_instruction(0xf355).unwrap();
+    assert_eq!(cpu.i, 1004);
+    cpu.emulate_instruction(0xf265).unwrap();
+    assert_eq!(cpu.i, 1007);
+
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::chip48());
+    cpu.i = 1000;
+    cpu.emulate_instruction(0xf355).unwrap();
+    assert_eq!(cpu.i, 1003);
+}
+
+#[test]
+fn test_quirk_jump_vx() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::schip());
+    cpu.v[0] = 0x10;
+    cpu.v[2] = 0x4;
+    cpu.emulate_instruction(0xb234).unwrap();
+    assert_eq!(cpu.pc, 0x238);
+}
+
+#[test]
+fn test_quirk_vf_reset() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::cosmac_vip());
+    for op in [0x8011, 0x8012, 0x8013].iter() {
+        cpu.v[0xf] = 1;
+        cpu.emulate_instruction(*op).unwrap();
+        assert_eq!(cpu.v[0xf], 0);
+    }
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.v[0xf] = 1;
+    cpu.emulate_instruction(0x8011).unwrap();
+    assert_eq!(cpu.v[0xf], 1);
+}
+
+#[test]
+fn test_quirk_clip_sprites() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::cosmac_vip());
+    cpu.i = 1000;
+    cpu.memory[1000] = 0xff;
+    cpu.memory[1001] = 0xff;
+    cpu.v[0] = 60;
+    cpu.v[1] = 31;
+    cpu.emulate_instruction(0xd012).unwrap();
+    assert_eq!(cpu.gfx[31][63], 1);
+    assert_eq!(cpu.gfx[31][0], 0);
+    assert_eq!(cpu.gfx[0][60], 0);
+
+    // the starting position still wraps
+    cpu.v[0] = 64 + 2;
+    cpu.v[1] = 0;
+    cpu.emulate_instruction(0xd011).unwrap();
+    assert_eq!(cpu.gfx[0][2], 1);
+    assert_eq!(cpu.gfx[0][1], 0);
+}
+
+#[test]
+fn test_quirk_presets() {
+    assert_eq!("vip".parse(), Ok(Quirks::cosmac_vip()));
+    assert_eq!("xochip".parse(), Ok(Quirks::xochip()));
+    assert!("nope".parse::<Quirks>().is_err());
+    assert_eq!(Quirks::for_platform(Platform::SuperChip), Quirks::schip());
+    assert_eq!(Quirks::for_platform(Platform::Chip8), Quirks::cosmac_vip());
+}
+
+#[test]
+fn test_seeded_random() {
+    let mut a = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    let mut b = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    a.seed_rng(1234);
+    b.seed_rng(1234);
+    for _ in 0..16 {
+        a.emulate_instruction(0xC0FF).unwrap();
+        b.emulate_instruction(0xC0FF).unwrap();
+        assert_eq!(a.v[0], b.v[0]);
+    }
+    a.emulate_instruction(0xC10F).unwrap();
+    assert_eq!(a.v[1] & 0xF0, 0);
+}
+
+#[test]
+fn test_quirk_vip_random() {
+    let quirks = Quirks {
+        vip_random: true,
+        ..Quirks::cosmac_vip()
+    };
+    let mut cpu = Cpu::initialize(&[0x11, 0x22, 0x33], quirks);
+    cpu.rng.r9 = 0x00D8;
+    cpu.emulate_instruction(0xC0FF).unwrap();
+    assert_eq!(cpu.v[0], 0x25);
+    // The ROM plays no part
+    cpu.memory[0x200..0x300].fill(0xFF);
+    cpu.rng.r9 = 0x00D8;
+    cpu.emulate_instruction(0xC10F).unwrap();
+    assert_eq!(cpu.v[1], 0x05);
+}
+
+#[test]
+fn test_memory_policy_fetch() {
+    let mut cpu = Cpu::initialize(&[0x00, 0xE0], Quirks::default());
+    cpu.set_memory_policy(MemoryPolicy::Fault);
+    // 3F00 in the last word skips off the end without reading past it
+    cpu.memory[0xFFE..0x1000].copy_from_slice(&[0x3F, 0x00]);
+    cpu.pc = 0xFFE;
+    cpu.emulate_cycle().unwrap();
+    assert_eq!(cpu.pc, 0x1002);
+    assert_eq!(
+        cpu.emulate_cycle(),
+        Err(Fault::MemoryOutOfRange {
+            pc: 0x1002,
+            addr: 0x1003
+        })
+    );
+}
+
+#[test]
+fn test_memory_policy() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    assert_eq!(cpu.memory_policy(), MemoryPolicy::Wrap);
+    cpu.i = 0xFFE;
+    cpu.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+    cpu.emulate_instruction(0xF355).unwrap();
+    assert_eq!(cpu.memory[0xFFE..0x1000], [1, 2]);
+    assert_eq!(cpu.memory[..2], [3, 4]);
+    cpu.emulate_instruction(0xF365).unwrap();
+    assert_eq!(cpu.v[..4], [1, 2, 3, 4]);
+
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_memory_policy(MemoryPolicy::Fault);
+    cpu.i = 0xFFE;
+    cpu.v[0] = 123;
+    assert_eq!(
+        cpu.emulate_instruction(0xF033),
+        Err(Fault::MemoryOutOfRange {
+            pc: 0x200,
+            addr: 0x1000
+        })
+    );
+    // Nothing was written, not even the bytes in range
+    assert_eq!(cpu.memory[0xFFE], 0);
+    assert_eq!(cpu.pc, 0x200);
+    assert!(cpu.emulate_instruction(0xD005).is_err());
+
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.set_memory_policy(MemoryPolicy::Ignore);
+    cpu.i = 0xFFF;
+    cpu.v[..2].copy_from_slice(&[7, 8]);
+    cpu.emulate_instruction(0xF155).unwrap();
+    assert_eq!(cpu.memory[0xFFF], 7);
+    assert_eq!(cpu.memory[0], FONT_SET[0]);
+    cpu.emulate_instruction(0xF165).unwrap();
+    assert_eq!(cpu.v[..2], [7, 0]);
+}
+
+#[test]
+fn test_add_i_wraps() {
+    let mut cpu = Cpu::initialize(&[1, 1, 1], Quirks::default());
+    cpu.i = 0xFFFF;
+    cpu.v[0] = 2;
+    cpu.emulate_instruction(0xF01E).unwrap();
+    assert_eq!(cpu.i, 1);
+}
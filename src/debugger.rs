@@ -0,0 +1,572 @@
+use std::fmt::Write;
+
+use crate::cpu::{Cpu, Fault};
+use crate::instruction::{decode_long, Instruction};
+
+/// Summary of the commands understood by [`Debugger::command`].
+pub const HELP: &str = "\
+  step [n]            s   execute n instructions (default 1)
+  next                n   step, running over a subroutine call
+  finish              o   run until the current subroutine returns
+  continue            c   run until a breakpoint or watch triggers
+  break <addr>        b   stop when the program counter reaches addr
+  break op <pattern>      stop before an opcode, e.g. `break op dxyx`
+  break if <cond>         stop when a condition holds, e.g. `break if v3 == 0x10`
+  watch <operand>     w   stop after an operand changes, e.g. `watch [0x3f0]`
+  delete [id]         d   remove a breakpoint, or all of them
+  info                    list breakpoints and watches
+  regs                r   show the registers
+  mem <addr> [len]    m   dump memory
+  set <operand> <val>     change a register or memory, e.g. `set v3 0x10`,
+                          `set [0x300] 0x12 0x34`
+  backtrace           bt  show the subroutine calls on the stack
+  where                   show the next instruction
+Operands are v0-vf, i, pc, sp, dt, st, [addr] (a byte in memory) and numbers.
+Timers only run while the game does, not while stepping.";
+
+// Something the debugger can read and write.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Operand {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+    Mem(u16),
+    Const(u16),
+}
+
+impl Operand {
+    fn parse(s: &str) -> Result<Operand, String> {
+        let s = s.trim().to_ascii_lowercase();
+        let op = match s.as_str() {
+            "i" => Operand::I,
+            "pc" => Operand::Pc,
+            "sp" => Operand::Sp,
+            "dt" => Operand::Dt,
+            "st" => Operand::St,
+            _ if s.starts_with('[') && s.ends_with(']') => {
+                Operand::Mem(parse_number(&s[1..s.len() - 1])?)
+            }
+            _ if s.len() == 2 && s.starts_with('v') => match u8::from_str_radix(&s[1..], 16) {
+                Ok(x) => Operand::V(x as usize),
+                Err(_) => return Err(format!("unknown register {:?}", s)),
+            },
+            _ => Operand::Const(parse_number(&s)?),
+        };
+        Ok(op)
+    }
+
+    fn get(self, cpu: &Cpu) -> u16 {
+        match self {
+            Operand::V(x) => cpu.v[x] as u16,
+            Operand::I => cpu.i,
+            Operand::Pc => cpu.pc,
+            Operand::Sp => cpu.sp,
+            Operand::Dt => cpu.delay_timer as u16,
+            Operand::St => cpu.sound_timer as u16,
+            Operand::Mem(addr) => cpu.peek(addr as usize) as u16,
+            Operand::Const(n) => n,
+        }
+    }
+
+    fn set(self, cpu: &mut Cpu, value: u16) -> Result<(), String> {
+        let byte = || {
+            if value > 0xFF {
+                Err(format!("{:#x} doesn't fit in a byte", value))
+            } else {
+                Ok(value as u8)
+            }
+        };
+        match self {
+            Operand::V(x) => cpu.v[x] = byte()?,
+            Operand::I => cpu.i = value,
+            Operand::Pc => cpu.pc = value,
+            Operand::Sp => {
+                if value as usize > cpu.stack_depth {
+                    return Err(format!("the stack only has {} entries", cpu.stack_depth));
+                }
+                cpu.sp = value
+            }
+            Operand::Dt => cpu.delay_timer = byte()?,
+            Operand::St => cpu.sound_timer = byte()?,
+            Operand::Mem(addr) => {
+                let addr = addr as usize % cpu.platform.memory_size();
+                cpu.memory[addr] = byte()?
+            }
+            Operand::Const(_) => return Err("can't assign to a number".to_string()),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+// `lhs op rhs`, e.g. `v3 == 0x10`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Condition {
+    lhs: Operand,
+    cmp: Cmp,
+    rhs: Operand,
+}
+
+impl Condition {
+    fn parse(s: &str) -> Result<Condition, String> {
+        // Two character operators first so `<=` isn't read as `<`
+        const OPS: [(&str, Cmp); 6] = [
+            ("==", Cmp::Eq),
+            ("!=", Cmp::Ne),
+            ("<=", Cmp::Le),
+            (">=", Cmp::Ge),
+            ("<", Cmp::Lt),
+            (">", Cmp::Gt),
+        ];
+        for &(text, cmp) in OPS.iter() {
+            if let Some(at) = s.find(text) {
+                return Ok(Condition {
+                    lhs: Operand::parse(&s[..at])?,
+                    cmp,
+                    rhs: Operand::parse(&s[at + text.len()..])?,
+                });
+            }
+        }
+        Err(format!("{:?} isn't a comparison", s.trim()))
+    }
+
+    fn holds(&self, cpu: &Cpu) -> bool {
+        let (a, b) = (self.lhs.get(cpu), self.rhs.get(cpu));
+        match self.cmp {
+            Cmp::Eq => a == b,
+            Cmp::Ne => a != b,
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Gt => a > b,
+            Cmp::Ge => a >= b,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    Pc(u16),
+    // Opcodes where `opcode & mask == value`
+    Opcode { mask: u16, value: u16 },
+    Condition(Condition),
+    Watch { operand: Operand, last: u16 },
+}
+
+struct Breakpoint {
+    id: usize,
+    kind: Kind,
+    // What the user typed, for `info`
+    text: String,
+}
+
+// What the debugger does while the game runs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Mode {
+    Paused,
+    Running,
+    // Until execution is back at `pc` with the same stack depth
+    StepOver { pc: u16, sp: u16 },
+    // Until the stack is shallower than `sp`
+    StepOut { sp: u16 },
+}
+
+/// An interactive debugger wrapped around a [`Cpu`].
+///
+/// The front end drives the game with [`run_frame`](Debugger::run_frame)
+/// instead of [`Cpu::run_frame`], and while the debugger
+/// [`is_paused`](Debugger::is_paused) feeds it commands typed by the user
+/// through [`command`](Debugger::command). See [`HELP`] for the commands.
+pub struct Debugger {
+    mode: Mode,
+    breakpoints: Vec<Breakpoint>,
+    next_id: usize,
+    // Set when resuming, so a breakpoint on the current instruction doesn't
+    // stop execution straight away
+    resumed: bool,
+}
+
+impl Debugger {
+    /// Creates a debugger that starts out paused.
+    pub fn new() -> Debugger {
+        Debugger {
+            mode: Mode::Paused,
+            breakpoints: Vec::new(),
+            next_id: 1,
+            resumed: false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    /// Stops the game, e.g. on a hotkey.
+    pub fn pause(&mut self) {
+        self.mode = Mode::Paused;
+    }
+
+    /// Runs a frame of `cpu` like [`Cpu::run_frame`], checking breakpoints
+    /// before and watches after every instruction. Does nothing while paused.
+    ///
+    /// Returns why execution stopped, if it did. Faults stop execution too
+    /// rather than ending the game, so the state can be inspected.
+    pub fn run_frame(&mut self, cpu: &mut Cpu) -> Option<String> {
+        if self.is_paused() {
+            return None;
+        }
+        for _ in 0..cpu.frame_cycles() {
+            if cpu.halted {
+                break;
+            }
+            if let Some(reason) = self.check_before(cpu) {
+                return Some(self.stop(cpu, reason));
+            }
+            if let Err(fault) = cpu.emulate_cycle() {
+                return Some(self.stop(cpu, fault.to_string()));
+            }
+            if let Some(reason) = self.check_after(cpu) {
+                return Some(self.stop(cpu, reason));
+            }
+        }
+        cpu.tick_timers();
+        if cpu.halted {
+            self.pause();
+            return Some("program exited".to_string());
+        }
+        None
+    }
+
+    /// Executes one command line and returns what to show the user.
+    ///
+    /// Commands that resume the game (`continue`, `finish`, a `next` over a
+    /// call) only change the mode, the front end then keeps calling
+    /// [`run_frame`](Debugger::run_frame) until it stops again.
+    pub fn command(&mut self, cpu: &mut Cpu, line: &str) -> Result<String, String> {
+        let line = line.trim();
+        let (cmd, rest) = match line.find(char::is_whitespace) {
+            Some(at) => (&line[..at], line[at..].trim()),
+            None => (line, ""),
+        };
+        let args: Vec<&str> = rest.split_whitespace().collect();
+
+        match cmd {
+            "" => Ok(String::new()),
+            "help" | "h" | "?" => Ok(HELP.to_string()),
+            "step" | "s" => {
+                let n = match args.first() {
+                    Some(n) => parse_number(n)?,
+                    None => 1,
+                };
+                for _ in 0..n {
+                    self.step(cpu)?;
+                }
+                Ok(location(cpu))
+            }
+            "next" | "n" => match current(cpu) {
+                Some(Instruction::Call(_)) => {
+                    self.mode = Mode::StepOver {
+                        pc: cpu.pc.wrapping_add(2),
+                        sp: cpu.sp,
+                    };
+                    self.resumed = true;
+                    Ok(String::new())
+                }
+                _ => {
+                    self.step(cpu)?;
+                    Ok(location(cpu))
+                }
+            },
+            "finish" | "o" => {
+                if cpu.sp == 0 {
+                    return Err("not inside a subroutine".to_string());
+                }
+                self.mode = Mode::StepOut { sp: cpu.sp };
+                self.resumed = true;
+                Ok(String::new())
+            }
+            "continue" | "c" => {
+                self.mode = Mode::Running;
+                self.resumed = true;
+                Ok(String::new())
+            }
+            "break" | "b" => {
+                let kind = match args.first() {
+                    Some(&"op") => {
+                        let (mask, value) = parse_pattern(args.get(1).unwrap_or(&""))?;
+                        Kind::Opcode { mask, value }
+                    }
+                    Some(&"if") => Kind::Condition(Condition::parse(&rest[2..])?),
+                    Some(addr) => Kind::Pc(parse_number(addr)?),
+                    None => return Err("break where?".to_string()),
+                };
+                Ok(self.add(kind, rest))
+            }
+            "watch" | "w" => {
+                let operand = Operand::parse(rest)?;
+                let last = operand.get(cpu);
+                Ok(self.add(Kind::Watch { operand, last }, rest))
+            }
+            "delete" | "d" => match args.first() {
+                Some(id) => {
+                    let id = parse_number(id)? as usize;
+                    let before = self.breakpoints.len();
+                    self.breakpoints.retain(|b| b.id != id);
+                    if self.breakpoints.len() == before {
+                        return Err(format!("no breakpoint {}", id));
+                    }
+                    Ok(String::new())
+                }
+                None => {
+                    self.breakpoints.clear();
+                    Ok(String::new())
+                }
+            },
+            "info" => {
+                let mut out = String::new();
+                for b in self.breakpoints.iter() {
+                    let what = match b.kind {
+                        Kind::Watch { .. } => "watch",
+                        _ => "break",
+                    };
+                    let _ = writeln!(out, "{}: {} {}", b.id, what, b.text);
+                }
+                Ok(out.trim_end().to_string())
+            }
+            "regs" | "r" => Ok(registers(cpu)),
+            "mem" | "m" => {
+                let addr = parse_number(args.first().ok_or("mem where?")?)? as usize;
+                let len = match args.get(1) {
+                    Some(n) => parse_number(n)? as usize,
+                    None => 64,
+                };
+                Ok(memory(cpu, addr, len))
+            }
+            "set" => {
+                if args.len() < 2 {
+                    return Err("usage: set <operand> <value>".to_string());
+                }
+                match Operand::parse(args[0])? {
+                    // Several bytes can be written in one go
+                    Operand::Mem(addr) => {
+                        for (k, v) in args[1..].iter().enumerate() {
+                            Operand::Mem(addr.wrapping_add(k as u16)).set(cpu, parse_number(v)?)?;
+                        }
+                    }
+                    operand => operand.set(cpu, Operand::parse(args[1])?.get(cpu))?,
+                }
+                self.rearm_watches(cpu);
+                Ok(String::new())
+            }
+            "backtrace" | "bt" => Ok(backtrace(cpu)),
+            "where" => Ok(location(cpu)),
+            _ => Err(format!("unknown command {:?}, try `help`", cmd)),
+        }
+    }
+
+    fn stop(&mut self, cpu: &Cpu, reason: String) -> String {
+        self.pause();
+        if reason.is_empty() {
+            location(cpu)
+        } else {
+            format!("{}\n{}", reason, location(cpu))
+        }
+    }
+
+    fn add(&mut self, kind: Kind, text: &str) -> String {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push(Breakpoint {
+            id,
+            kind,
+            text: text.to_string(),
+        });
+        format!("breakpoint {}", id)
+    }
+
+    // Executes a single instruction, ignoring breakpoints.
+    fn step(&mut self, cpu: &mut Cpu) -> Result<(), String> {
+        if cpu.halted {
+            return Err("program exited".to_string());
+        }
+        cpu.emulate_cycle().map_err(|f: Fault| f.to_string())?;
+        self.rearm_watches(cpu);
+        Ok(())
+    }
+
+    // Watches compare against the value the user last saw.
+    fn rearm_watches(&mut self, cpu: &Cpu) {
+        for b in self.breakpoints.iter_mut() {
+            if let Kind::Watch {
+                operand,
+                ref mut last,
+            } = b.kind
+            {
+                *last = operand.get(cpu);
+            }
+        }
+    }
+
+    fn check_before(&mut self, cpu: &Cpu) -> Option<String> {
+        // Nothing gets fetched while FX0A waits for a key
+        if cpu.wait_key {
+            return None;
+        }
+        if self.resumed {
+            self.resumed = false;
+            return None;
+        }
+        let opcode = cpu.word_at(cpu.pc);
+        for b in self.breakpoints.iter() {
+            let hit = match b.kind {
+                Kind::Pc(pc) => cpu.pc == pc,
+                Kind::Opcode { mask, value } => opcode & mask == value,
+                Kind::Condition(c) => c.holds(cpu),
+                Kind::Watch { .. } => false,
+            };
+            if hit {
+                return Some(format!("breakpoint {}: {}", b.id, b.text));
+            }
+        }
+        None
+    }
+
+    fn check_after(&mut self, cpu: &Cpu) -> Option<String> {
+        self.resumed = false;
+        for b in self.breakpoints.iter_mut() {
+            if let Kind::Watch {
+                operand,
+                ref mut last,
+            } = b.kind
+            {
+                let now = operand.get(cpu);
+                if now != *last {
+                    let old = *last;
+                    *last = now;
+                    return Some(format!(
+                        "watch {}: {} changed from {:#x} to {:#x}",
+                        b.id, b.text, old, now
+                    ));
+                }
+            }
+        }
+        match self.mode {
+            Mode::StepOver { pc, sp } if cpu.pc == pc && cpu.sp == sp => Some(String::new()),
+            Mode::StepOut { sp } if cpu.sp < sp => Some(String::new()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Debugger {
+        Debugger::new()
+    }
+}
+
+fn parse_number(s: &str) -> Result<u16, String> {
+    let s = s.trim();
+    let r = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u16::from_str_radix(hex, 16)
+    } else {
+        s.parse()
+    };
+    r.map_err(|_| format!("{:?} isn't a number", s))
+}
+
+// Four hex digits where `x` matches anything, e.g. `dxy0` or `0x1xxx`.
+fn parse_pattern(s: &str) -> Result<(u16, u16), String> {
+    let digits = match s.len() {
+        6 => s.strip_prefix("0x").unwrap_or(s),
+        _ => s,
+    };
+    if digits.len() != 4 {
+        return Err(format!("{:?} isn't a 4 digit opcode pattern", s));
+    }
+    let (mut mask, mut value) = (0, 0);
+    for c in digits.chars() {
+        mask <<= 4;
+        value <<= 4;
+        if let Some(d) = c.to_digit(16) {
+            mask |= 0xF;
+            value |= d as u16;
+        } else if !c.is_ascii_alphabetic() {
+            return Err(format!("{:?} isn't a 4 digit opcode pattern", s));
+        }
+    }
+    Ok((mask, value))
+}
+
+// The instruction at the program counter, if it decodes.
+fn current(cpu: &Cpu) -> Option<Instruction> {
+    decode_long(cpu.word_at(cpu.pc), cpu.word_at(cpu.pc.wrapping_add(2))).ok()
+}
+
+fn location(cpu: &Cpu) -> String {
+    let opcode = cpu.word_at(cpu.pc);
+    match current(cpu) {
+        Some(ins) => format!("{:#06x}: {:04x}  {}", cpu.pc, opcode, ins),
+        None => format!("{:#06x}: {:04x}  ???", cpu.pc, opcode),
+    }
+}
+
+fn registers(cpu: &Cpu) -> String {
+    let mut out = format!(
+        "pc {:#06x}  i {:#06x}  sp {}  dt {}  st {}\n",
+        cpu.pc, cpu.i, cpu.sp, cpu.delay_timer, cpu.sound_timer
+    );
+    for (x, v) in cpu.v.iter().enumerate() {
+        let _ = write!(
+            out,
+            "v{:x} {:02x}{}",
+            x,
+            v,
+            if x % 8 == 7 { "\n" } else { "  " }
+        );
+    }
+    out.trim_end().to_string()
+}
+
+fn memory(cpu: &Cpu, addr: usize, len: usize) -> String {
+    let size = cpu.platform.memory_size();
+    let mut out = String::new();
+    for row in (0..len).step_by(16) {
+        let _ = write!(out, "{:#06x}:", (addr + row) % size);
+        for k in row..(row + 16).min(len) {
+            let _ = write!(out, " {:02x}", cpu.peek(addr + k));
+        }
+        out.push('\n');
+    }
+    out.trim_end().to_string()
+}
+
+// Each stack entry is a return address, the call sits just before it.
+fn backtrace(cpu: &Cpu) -> String {
+    let mut out = format!("#0  {:#06x}", cpu.pc);
+    let depth = (cpu.sp as usize).min(cpu.stack.len());
+    for (n, &ret) in cpu.stack[..depth].iter().rev().enumerate() {
+        let _ = write!(
+            out,
+            "\n#{}  {:#06x}  (returns to {:#06x})",
+            n + 1,
+            ret.wrapping_sub(2),
+            ret
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+#[path = "./debugger_tests.rs"]
+mod debugger_tests;
@@ -0,0 +1,179 @@
+use super::*;
+use crate::cpu::Platform;
+use crate::quirks::Quirks;
+
+// 0x200: 6005  V0 = 5
+// 0x202: 2208  call 0x208
+// 0x204: 7101  V1 += 1
+// 0x206: 1204  loop
+// 0x208: 6310  V3 = 0x10
+// 0x20a: 00ee  return
+const ROM: [u8; 12] = [
+    0x60, 0x05, 0x22, 0x08, 0x71, 0x01, 0x12, 0x04, 0x63, 0x10, 0x00, 0xEE,
+];
+
+fn setup() -> (Debugger, Cpu) {
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    cpu.set_clock_hz(600);
+    (Debugger::new(), cpu)
+}
+
+// Runs frames until the debugger stops, returning why.
+fn run(d: &mut Debugger, cpu: &mut Cpu) -> String {
+    for _ in 0..100 {
+        if let Some(reason) = d.run_frame(cpu) {
+            return reason;
+        }
+    }
+    panic!("the debugger never stopped");
+}
+
+#[test]
+fn test_step() {
+    let (mut d, mut cpu) = setup();
+    assert!(d.is_paused());
+    let out = d.command(&mut cpu, "step").unwrap();
+    assert_eq!(cpu.v[0], 5);
+    assert!(out.starts_with("0x0202: 2208"), "{}", out);
+    d.command(&mut cpu, "s 2").unwrap();
+    assert_eq!(cpu.pc, 0x20a);
+    assert!(d.is_paused());
+}
+
+#[test]
+fn test_pc_breakpoint() {
+    let (mut d, mut cpu) = setup();
+    assert_eq!(d.command(&mut cpu, "break 0x208").unwrap(), "breakpoint 1");
+    d.command(&mut cpu, "continue").unwrap();
+    let reason = run(&mut d, &mut cpu);
+    assert!(reason.starts_with("breakpoint 1"), "{}", reason);
+    assert_eq!(cpu.pc, 0x208);
+    assert!(d.is_paused());
+    assert!(d.run_frame(&mut cpu).is_none());
+
+    // Continuing from a breakpoint doesn't stop on it again
+    d.command(&mut cpu, "d 1").unwrap();
+    d.command(&mut cpu, "break 0x206").unwrap();
+    d.command(&mut cpu, "c").unwrap();
+    run(&mut d, &mut cpu);
+    assert_eq!(cpu.pc, 0x206);
+    d.command(&mut cpu, "c").unwrap();
+    run(&mut d, &mut cpu);
+    assert_eq!(cpu.pc, 0x206);
+    assert_eq!(cpu.v[1], 2);
+}
+
+#[test]
+fn test_opcode_and_condition_breakpoints() {
+    let (mut d, mut cpu) = setup();
+    d.command(&mut cpu, "break op 6xyx").unwrap();
+    d.command(&mut cpu, "c").unwrap();
+    run(&mut d, &mut cpu);
+    // The first instruction is skipped when resuming
+    assert_eq!(cpu.pc, 0x208);
+
+    let (mut d, mut cpu) = setup();
+    d.command(&mut cpu, "break if v1 >= 3").unwrap();
+    d.command(&mut cpu, "c").unwrap();
+    run(&mut d, &mut cpu);
+    assert_eq!(cpu.v[1], 3);
+    assert!(d.command(&mut cpu, "break if v1").is_err());
+}
+
+#[test]
+fn test_watch() {
+    let (mut d, mut cpu) = setup();
+    d.command(&mut cpu, "watch v3").unwrap();
+    d.command(&mut cpu, "c").unwrap();
+    let reason = run(&mut d, &mut cpu);
+    assert!(reason.contains("changed from 0x0 to 0x10"), "{}", reason);
+    assert_eq!(cpu.pc, 0x20a);
+}
+
+#[test]
+fn test_next_and_finish() {
+    let (mut d, mut cpu) = setup();
+    d.command(&mut cpu, "s").unwrap();
+    // Running over the call at 0x202
+    d.command(&mut cpu, "next").unwrap();
+    assert!(!d.is_paused());
+    run(&mut d, &mut cpu);
+    assert_eq!(cpu.pc, 0x204);
+    assert_eq!(cpu.v[3], 0x10);
+
+    // A plain instruction is just stepped
+    d.command(&mut cpu, "n").unwrap();
+    assert_eq!(cpu.pc, 0x206);
+    assert!(d.is_paused());
+
+    let (mut d, mut cpu) = setup();
+    assert!(d.command(&mut cpu, "finish").is_err());
+    d.command(&mut cpu, "s 2").unwrap();
+    assert_eq!(
+        d.command(&mut cpu, "bt").unwrap(),
+        "#0  0x0208\n#1  0x0202  (returns to 0x0204)"
+    );
+    d.command(&mut cpu, "finish").unwrap();
+    run(&mut d, &mut cpu);
+    assert_eq!(cpu.pc, 0x204);
+    assert_eq!(cpu.sp, 0);
+}
+
+#[test]
+fn test_next_at_end_of_memory() {
+    let (mut d, mut cpu) = setup();
+    cpu.set_platform(Platform::XoChip);
+    cpu.memory[0xFFFE..].copy_from_slice(&[0x22, 0x08]);
+    cpu.pc = 0xFFFE;
+    // The call returns to the start of memory
+    d.command(&mut cpu, "next").unwrap();
+    run(&mut d, &mut cpu);
+    assert_eq!(cpu.pc, 0x0000);
+    assert_eq!(cpu.sp, 0);
+}
+
+#[test]
+fn test_inspect_and_edit() {
+    let (mut d, mut cpu) = setup();
+    d.command(&mut cpu, "set v3 0x42").unwrap();
+    d.command(&mut cpu, "set i 0x300").unwrap();
+    d.command(&mut cpu, "set [0x300] 0x12 0x34").unwrap();
+    d.command(&mut cpu, "set v4 v3").unwrap();
+    assert_eq!(cpu.v[3], 0x42);
+    assert_eq!(cpu.v[4], 0x42);
+    assert_eq!(cpu.i, 0x300);
+    assert_eq!(cpu.memory[0x301], 0x34);
+    assert!(d.command(&mut cpu, "set v3 0x100").is_err());
+
+    let mem = d.command(&mut cpu, "mem 0x300 4").unwrap();
+    assert_eq!(mem, "0x0300: 12 34 00 00");
+    let regs = d.command(&mut cpu, "regs").unwrap();
+    assert!(regs.contains("i 0x0300"), "{}", regs);
+    assert!(regs.contains("v3 42"), "{}", regs);
+}
+
+#[test]
+fn test_fault_pauses() {
+    let mut cpu = Cpu::initialize(&[0xFF, 0xFF], Quirks::default());
+    let mut d = Debugger::new();
+    d.command(&mut cpu, "c").unwrap();
+    let reason = run(&mut d, &mut cpu);
+    assert!(reason.starts_with("invalid opcode"), "{}", reason);
+    assert!(d.is_paused());
+    assert!(d.command(&mut cpu, "s").is_err());
+}
+
+#[test]
+fn test_info_and_delete() {
+    let (mut d, mut cpu) = setup();
+    d.command(&mut cpu, "b 0x204").unwrap();
+    d.command(&mut cpu, "w [0x300]").unwrap();
+    assert_eq!(
+        d.command(&mut cpu, "info").unwrap(),
+        "1: break 0x204\n2: watch [0x300]"
+    );
+    assert!(d.command(&mut cpu, "delete 7").is_err());
+    d.command(&mut cpu, "delete").unwrap();
+    assert_eq!(d.command(&mut cpu, "info").unwrap(), "");
+    assert!(d.command(&mut cpu, "frobnicate").is_err());
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::cpu::Platform;
+use crate::instruction::{decode_long, Instruction};
+
+/// Where ROMs are loaded and start executing.
+pub const ROM_START: u16 = 0x200;
+
+/// What a labelled address holds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Label {
+    /// The target of a jump or call.
+    Code,
+    /// The start of a data region, or an address `I` is pointed at.
+    Data,
+}
+
+/// A ROM split into code and data by following its control flow.
+///
+/// Tracing starts at 0x200 and follows jumps, calls and both sides of skips.
+/// Whatever isn't reached that way is treated as data. `BNNN` jumps go
+/// through a computed offset, only their base address is followed.
+pub struct Disassembly {
+    /// The instructions found, by address.
+    pub code: BTreeMap<u16, Instruction>,
+    /// Labelled addresses, all of them inside the ROM and at the start of an
+    /// instruction or data run.
+    pub labels: BTreeMap<u16, Label>,
+    rom: Vec<u8>,
+}
+
+impl Disassembly {
+    /// Traces `rom` as loaded at 0x200, decoding the instructions `platform`
+    /// supports.
+    pub fn trace(rom: &[u8], platform: Platform) -> Disassembly {
+        let start = ROM_START as usize;
+        let end = start + rom.len();
+        let word = |a: usize| -> u16 {
+            let byte = |a: usize| {
+                if a >= start && a < end {
+                    rom[a - start] as u16
+                } else {
+                    0
+                }
+            };
+            byte(a) << 8 | byte(a + 1)
+        };
+
+        let mut code = BTreeMap::new();
+        let mut covered = vec![false; rom.len()];
+        let mut refs: Vec<(u16, Label)> = Vec::new();
+        let mut todo = vec![ROM_START];
+        while let Some(addr) = todo.pop() {
+            let a = addr as usize;
+            if a < start || a + 2 > end || code.contains_key(&addr) {
+                continue;
+            }
+            let ins = match decode_long(word(a), word(a + 2)) {
+                Ok(ins) if platform.supports(&ins) => ins,
+                _ => continue,
+            };
+            let size = ins.size() as usize;
+            // Instructions overlapping others can't be listed
+            if a + size > end || covered[a - start..a - start + size].contains(&true) {
+                continue;
+            }
+            covered[a - start..a - start + size]
+                .iter_mut()
+                .for_each(|c| *c = true);
+            code.insert(addr, ins);
+
+            // Nothing follows the last word of memory
+            let next = addr.checked_add(size as u16);
+            match ins {
+                Instruction::Ret | Instruction::Exit => {}
+                Instruction::Jp(t) | Instruction::JpV0(t) => {
+                    refs.push((t, Label::Code));
+                    todo.push(t);
+                }
+                Instruction::Call(t) => {
+                    refs.push((t, Label::Code));
+                    todo.push(t);
+                    todo.extend(next);
+                }
+                Instruction::LdI(t) | Instruction::LdILong(t) => {
+                    refs.push((t, Label::Data));
+                    todo.extend(next);
+                }
+                Instruction::SeByte { .. }
+                | Instruction::SneByte { .. }
+                | Instruction::SeReg { .. }
+                | Instruction::SneReg { .. }
+                | Instruction::Skp(_)
+                | Instruction::Sknp(_) => {
+                    // XO-CHIP skips the whole 4 byte F000 NNNN
+                    let long = next.is_some_and(|n| word(n as usize) == 0xF000);
+                    let skipped = if platform == Platform::XoChip && long {
+                        4
+                    } else {
+                        2
+                    };
+                    todo.extend(next);
+                    todo.extend(next.and_then(|n| n.checked_add(skipped)));
+                }
+                _ => todo.extend(next),
+            }
+        }
+
+        // Only addresses where the listing has a line can carry a label
+        let mut labels = BTreeMap::new();
+        let mut in_data = false;
+        for a in start..end {
+            let addr = a as u16;
+            if code.contains_key(&addr) {
+                in_data = false;
+            } else if !covered[a - start] {
+                if !in_data {
+                    labels.insert(addr, Label::Data);
+                }
+                in_data = true;
+            }
+        }
+        for (addr, kind) in refs {
+            let a = addr as usize;
+            if a < start || a >= end {
+                continue;
+            }
+            if code.contains_key(&addr) {
+                labels.insert(addr, Label::Code);
+            } else if !covered[a - start] {
+                labels.entry(addr).or_insert(kind);
+            }
+        }
+
+        Disassembly {
+            code,
+            labels,
+            rom: rom.to_vec(),
+        }
+    }
+
+    /// The name of the label at `addr`, e.g. `code_208` or `data_2a0`.
+    pub fn label(&self, addr: u16) -> Option<String> {
+        self.labels.get(&addr).map(|kind| match kind {
+            Label::Code => format!("code_{:03x}", addr),
+            Label::Data => format!("data_{:03x}", addr),
+        })
+    }
+
+    // The instruction with its address operand replaced by a label, if
+    // there is one.
+    fn mnemonic(&self, ins: Instruction) -> String {
+        let (prefix, addr) = match ins {
+            Instruction::Jp(t) => ("JP ", t),
+            Instruction::Call(t) => ("CALL ", t),
+            Instruction::JpV0(t) => ("JP V0, ", t),
+            Instruction::LdI(t) => ("LD I, ", t),
+            Instruction::LdILong(t) => ("LD I, LONG ", t),
+            _ => return ins.to_string(),
+        };
+        match self.label(addr) {
+            Some(label) => format!("{}{}", prefix, label),
+            None => ins.to_string(),
+        }
+    }
+}
+
+// One line per instruction and up to 8 bytes per `db`, each commented with
+// its address and the raw bytes.
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let start = ROM_START as usize;
+        let end = start + self.rom.len();
+        let mut a = start;
+        while a < end {
+            let addr = a as u16;
+            if let Some(label) = self.label(addr) {
+                writeln!(f, "{}:", label)?;
+            }
+            if let Some(&ins) = self.code.get(&addr) {
+                let size = ins.size() as usize;
+                let bytes = &self.rom[a - start..a - start + size];
+                let hex: Vec<String> = bytes
+                    .chunks(2)
+                    .map(|w| format!("{:02x}{:02x}", w[0], w[1]))
+                    .collect();
+                writeln!(
+                    f,
+                    "    {:<24} ; {:03x}: {}",
+                    self.mnemonic(ins),
+                    a,
+                    hex.join(" ")
+                )?;
+                a += size;
+                continue;
+            }
+
+            // A data run stops at the next line that needs a label
+            let mut run = a + 1;
+            while run < end
+                && run - a < 8
+                && !self.code.contains_key(&(run as u16))
+                && !self.labels.contains_key(&(run as u16))
+            {
+                run += 1;
+            }
+            let bytes: Vec<String> = self.rom[a - start..run - start]
+                .iter()
+                .map(|b| format!("{:#04x}", b))
+                .collect();
+            writeln!(
+                f,
+                "    {:<24} ; {:03x}",
+                format!("db {}", bytes.join(", ")),
+                a
+            )?;
+            a = run;
+        }
+        Ok(())
+    }
+}
+
+/// Disassembles `rom` into source the assembler accepts, see [`Disassembly`].
+pub fn disassemble(rom: &[u8], platform: Platform) -> String {
+    Disassembly::trace(rom, platform).to_string()
+}
+
+#[cfg(test)]
+#[path = "./disasm_tests.rs"]
+mod disasm_tests;
@@ -0,0 +1,116 @@
+use super::*;
+
+// 0x200: 2206  call 0x206
+// 0x202: 1202  loop forever
+// 0x204: 12 34 (data)
+// 0x206: A20E  LD I, 0x20e
+// 0x208: 3001  skip if V0 == 1
+// 0x20a: D015  draw
+// 0x20c: 00EE  return
+// 0x20e: F0 90 (sprite data)
+const ROM: [u8; 16] = [
+    0x22, 0x06, 0x12, 0x02, 0x12, 0x34, 0xA2, 0x0E, 0x30, 0x01, 0xD0, 0x15, 0x00, 0xEE, 0xF0, 0x90,
+];
+
+#[test]
+fn test_mnemonics() {
+    let cases = [
+        (0x6310, "LD V3, 0x10"),
+        (0xD015, "DRW V0, V1, 5"),
+        (0x00E0, "CLS"),
+        (0x8AB6, "SHR VA, VB"),
+        (0xF155, "LD [I], V1"),
+        (0xF265, "LD V2, [I]"),
+        (0xB300, "JP V0, 0x300"),
+        (0xE19E, "SKP V1"),
+        (0x00C4, "SCD 4"),
+    ];
+    for &(opcode, text) in cases.iter() {
+        assert_eq!(
+            crate::instruction::decode(opcode).unwrap().to_string(),
+            text
+        );
+    }
+}
+
+#[test]
+fn test_trace() {
+    let d = Disassembly::trace(&ROM, Platform::Chip8);
+    let starts: Vec<u16> = d.code.keys().cloned().collect();
+    assert_eq!(starts, vec![0x200, 0x202, 0x206, 0x208, 0x20a, 0x20c]);
+    assert_eq!(d.labels.get(&0x202), Some(&Label::Code));
+    assert_eq!(d.labels.get(&0x206), Some(&Label::Code));
+    assert_eq!(d.labels.get(&0x204), Some(&Label::Data));
+    assert_eq!(d.labels.get(&0x20e), Some(&Label::Data));
+    assert_eq!(d.labels.get(&0x200), None);
+}
+
+#[test]
+fn test_listing() {
+    let listing = disassemble(&ROM, Platform::Chip8);
+    let lines: Vec<&str> = listing.lines().map(|l| l.trim_end()).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "    CALL code_206            ; 200: 2206",
+            "code_202:",
+            "    JP code_202              ; 202: 1202",
+            "data_204:",
+            "    db 0x12, 0x34            ; 204",
+            "code_206:",
+            "    LD I, data_20e           ; 206: a20e",
+            "    SE V0, 0x01              ; 208: 3001",
+            "    DRW V0, V1, 5            ; 20a: d015",
+            "    RET                      ; 20c: 00ee",
+            "data_20e:",
+            "    db 0xf0, 0x90            ; 20e",
+        ]
+    );
+}
+
+#[test]
+fn test_platform_opcodes() {
+    // 00FF is only an instruction on SCHIP, F000 NNNN only on XO-CHIP
+    let rom = [0x00, 0xFF, 0xF0, 0x00, 0x02, 0x08, 0x12, 0x06];
+    let chip8 = Disassembly::trace(&rom, Platform::Chip8);
+    assert!(chip8.code.is_empty());
+    let schip = Disassembly::trace(&rom, Platform::SuperChip);
+    assert_eq!(schip.code.len(), 1);
+    let xo = Disassembly::trace(&rom, Platform::XoChip);
+    assert_eq!(xo.code.get(&0x202), Some(&Instruction::LdILong(0x0208)));
+    assert_eq!(xo.code.len(), 3);
+}
+
+#[test]
+fn test_xochip_skip_over_long() {
+    // 3000 skips the whole F000 NNNN, landing on 00E0
+    let rom = [0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0, 0x12, 0x06];
+    let d = Disassembly::trace(&rom, Platform::XoChip);
+    let starts: Vec<u16> = d.code.keys().cloned().collect();
+    assert_eq!(starts, vec![0x200, 0x202, 0x206, 0x208]);
+}
+
+#[test]
+fn test_end_of_memory() {
+    // 6000 all the way up, with 3000 at 0xFFFC skipping off the end
+    let mut rom = [0x60, 0x00].repeat(0x7F00);
+    let skip = rom.len() - 4;
+    rom[skip] = 0x30;
+    let d = Disassembly::trace(&rom, Platform::XoChip);
+    assert_eq!(
+        d.code.get(&0xFFFC),
+        Some(&Instruction::SeByte { x: 0, nn: 0 })
+    );
+    assert_eq!(
+        d.code.get(&0xFFFE),
+        Some(&Instruction::LdByte { x: 0, nn: 0 })
+    );
+}
+
+#[test]
+fn test_long_data_line() {
+    let rom = [0x12, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA];
+    let listing = disassemble(&rom, Platform::Chip8);
+    // Wider than the column, the comment still gets a space before it
+    assert!(listing.contains("0xaa, 0xaa ; 202\n"), "{}", listing);
+}
@@ -1,81 +1,127 @@
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::WindowCanvas;
-use sdl2::EventPump;
+use sdl2::video::FullscreenType;
+use sdl2::{EventPump, Sdl};
 
-pub const WIDTH: u32 = 64;
-pub const HEIGHT: u32 = 32;
-const BOX_SIZE: u32 = 10;
+use chip8emu::cpu::{HEIGHT, HIRES_HEIGHT, HIRES_WIDTH, WIDTH};
+use chip8emu::palette::Palette;
 
 pub struct Display {
+    pub sdl_context: Sdl,
     pub event_pump: EventPump,
     pub canvas: WindowCanvas,
+    pub palette: Palette,
+    /// Only scale the screen by whole numbers, for evenly sized pixels.
+    pub integer_scale: bool,
 }
 
 impl Display {
-    pub fn new() -> Display {
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsystem = sdl_context.video().unwrap();
+    // A resizable window `scale` times the size of the 64x32 screen.
+    // On hi-DPI screens the window is sized in points and drawn in pixels.
+    // Fails with SDL's reason if there's no window to be had.
+    pub fn new(
+        title: &str,
+        palette: Palette,
+        scale: u32,
+        fullscreen: bool,
+    ) -> Result<Display, String> {
+        let sdl_context = sdl2::init()?;
+        let video_subsystem = sdl_context.video()?;
 
-        let window = video_subsystem
-            .window("chip8emu by glodi", WIDTH * BOX_SIZE, HEIGHT * BOX_SIZE)
+        let mut builder = video_subsystem.window(title, WIDTH * scale, HEIGHT * scale);
+        builder
             .position_centered()
-            .opengl()
-            .build()
-            .unwrap();
+            .resizable()
+            .allow_highdpi()
+            .opengl();
+        if fullscreen {
+            builder.fullscreen_desktop();
+        }
+        let mut window = builder.build().map_err(|e| e.to_string())?;
+        let _ = window.set_minimum_size(WIDTH, HEIGHT);
 
-        let mut canvas = window.into_canvas().build().unwrap();
+        let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
 
         canvas.set_draw_color(Color::RGB(0, 0, 0));
         canvas.clear();
         canvas.present();
-        let event_pump = sdl_context.event_pump().unwrap();
+        let event_pump = sdl_context.event_pump()?;
+
+        Ok(Display {
+            sdl_context,
+            event_pump,
+            canvas,
+            palette,
+            integer_scale: false,
+        })
+    }
 
-        Display {
-            event_pump: event_pump,
-            canvas: canvas,
+    // The area a width x height screen is drawn in: as large as fits in the
+    // window with a 2:1 ratio, centred, with black bars around it
+    fn screen_rect(&self, width: u32, height: u32) -> Rect {
+        let (w, h) = self.canvas.output_size().unwrap_or((WIDTH, HEIGHT));
+        let mut scale = (w as f32 / width as f32).min(h as f32 / height as f32);
+        if self.integer_scale && scale >= 1.0 {
+            scale = scale.floor();
         }
+        let (sw, sh) = (
+            (width as f32 * scale) as u32,
+            (height as f32 * scale) as u32,
+        );
+        Rect::new(
+            ((w - sw.min(w)) / 2) as i32,
+            ((h - sh.min(h)) / 2) as i32,
+            sw.max(1),
+            sh.max(1),
+        )
     }
 
-    // edit self.canvas so that it reflects the current state of gfx
-    pub fn set_frame(&mut self, &gfx: &[[u8; (WIDTH as usize)]; (HEIGHT as usize)]) {
-        for (n, el) in gfx.to_vec().iter().enumerate() {
-            for (s, &c) in el.iter().enumerate() {
-                let x = (s as u32) * BOX_SIZE;
-                let y = (n as u32) * BOX_SIZE;
+    // edit self.canvas so that it reflects the current state of gfx,
+    // of which only the top-left width x height pixels are on screen
+    pub fn set_frame(
+        &mut self,
+        gfx: &[[u8; HIRES_WIDTH as usize]; HIRES_HEIGHT as usize],
+        width: usize,
+        height: usize,
+    ) {
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+
+        // Pixel edges are rounded from the exact scale so that pixels tile
+        // the screen without gaps whatever its size
+        let screen = self.screen_rect(width as u32, height as u32);
+        let edge_x = |s: usize| screen.x() + (s as u32 * screen.width() / width as u32) as i32;
+        let edge_y = |n: usize| screen.y() + (n as u32 * screen.height() / height as u32) as i32;
 
-                if c == 0 {
-                    self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-                } else {
-                    self.canvas.set_draw_color(Color::RGB(0, 250, 0));
+        for (n, el) in gfx[..height].iter().enumerate() {
+            let (y0, y1) = (edge_y(n), edge_y(n + 1));
+            for (s, &c) in el[..width].iter().enumerate() {
+                let (x0, x1) = (edge_x(s), edge_x(s + 1));
+                if x1 == x0 || y1 == y0 {
+                    continue;
                 }
 
-                let _ = self
-                    .canvas
-                    .fill_rect(Rect::new(x as i32, y as i32, BOX_SIZE, BOX_SIZE));
+                let (r, g, b) = self.palette.color(c);
+                self.canvas.set_draw_color(Color::RGB(r, g, b));
+
+                let _ =
+                    self.canvas
+                        .fill_rect(Rect::new(x0, y0, (x1 - x0) as u32, (y1 - y0) as u32));
             }
         }
     }
 }
 
-// http://devernay.free.fr/hacks/chip8/C8TECH10.HTM#font
-//
-// Font set defines how to draw each digit.
-pub const FONT_SET: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
+// Switches the window of `canvas` between a window and the whole desktop.
+// Takes the canvas alone so it can be called while events are being polled.
+pub fn toggle_fullscreen(canvas: &mut WindowCanvas) {
+    let window = canvas.window_mut();
+    let mode = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off,
+    };
+    if let Err(e) = window.set_fullscreen(mode) {
+        eprintln!("couldn't change fullscreen mode: {}", e);
+    }
+}
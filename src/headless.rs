@@ -0,0 +1,194 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::iter::Peekable;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::cpu::{Cpu, Fault};
+use crate::movie::{checksum, Movie, MovieError};
+use crate::palette::Palette;
+
+/// A key press or release from an input script.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// Frame the change happens before, counting from 0.
+    pub frame: u64,
+    /// Keypad key, 0x0 - 0xF.
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// Parses an input script: one `<frame> press|release <key>` per line, the
+/// key being a hex digit. Blank lines and `#` comments are ignored.
+///
+/// ```
+/// use chip8emu::headless::parse_script;
+///
+/// let events = parse_script("# start the game\n30 press 5\n32 release 5\n").unwrap();
+/// assert_eq!(events.len(), 2);
+/// ```
+pub fn parse_script(text: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut events = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let err = |what: &str| format!("line {}: {}", n + 1, what);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() != 3 {
+            return Err(err("expected `<frame> press|release <key>`"));
+        }
+        let frame = words[0]
+            .parse()
+            .map_err(|_| err(&format!("{:?} isn't a frame number", words[0])))?;
+        let pressed = match words[1] {
+            "press" => true,
+            "release" => false,
+            w => return Err(err(&format!("{:?} isn't press or release", w))),
+        };
+        let key = match u8::from_str_radix(words[2], 16) {
+            Ok(k) if k < 16 => k,
+            _ => return Err(err(&format!("{:?} isn't a key from 0 to F", words[2]))),
+        };
+        events.push(KeyEvent {
+            frame,
+            key,
+            pressed,
+        });
+    }
+    // Keep the order of same-frame events as written
+    events.sort_by_key(|e| e.frame);
+    Ok(events)
+}
+
+/// How a headless run ended.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// All frames ran.
+    Completed,
+    /// The ROM exited with `00FD` before the last frame.
+    Exited,
+    /// The ROM hit a fault.
+    Fault(Fault),
+    /// The run took longer than its wall-clock limit.
+    Timeout,
+    /// A replayed movie went out of sync in this frame.
+    Diverged(u64),
+}
+
+/// Runs `cpu` for up to `frames` frames as fast as possible, applying
+/// `script` along the way. Returns how the run ended and how many frames
+/// ran.
+pub fn run(
+    cpu: &mut Cpu,
+    frames: u64,
+    script: &[KeyEvent],
+    timeout: Option<Duration>,
+) -> (Outcome, u64) {
+    let mut events = script.iter().peekable();
+    let input = |cpu: &mut Cpu, frame| apply_events(cpu, &mut events, frame);
+    run_frames(cpu, frames, timeout, input, |_, _| None)
+}
+
+/// Plays `movie` back on `cpu`, which must be fresh from
+/// [`Cpu::initialize`], stopping at the first frame that doesn't match the
+/// recording.
+pub fn replay(
+    cpu: &mut Cpu,
+    movie: &Movie,
+    timeout: Option<Duration>,
+) -> Result<(Outcome, u64), MovieError> {
+    movie.prepare(cpu)?;
+    let mut events = movie.events.iter().peekable();
+    let input = |cpu: &mut Cpu, frame| apply_events(cpu, &mut events, frame);
+    let check = |cpu: &Cpu, frame| match movie.checksums.get(&frame) {
+        Some(&sum) if sum != checksum(cpu) => Some(Outcome::Diverged(frame)),
+        _ => None,
+    };
+    Ok(run_frames(cpu, movie.frames, timeout, input, check))
+}
+
+// Presses and releases the keys of the events due by `frame`.
+fn apply_events<'a>(
+    cpu: &mut Cpu,
+    events: &mut Peekable<impl Iterator<Item = &'a KeyEvent>>,
+    frame: u64,
+) {
+    while let Some(e) = events.next_if(|e| e.frame <= frame) {
+        if e.pressed {
+            cpu.press_key(e.key);
+        } else {
+            cpu.release_key(e.key);
+        }
+    }
+}
+
+// Runs frames, calling `input` before each one and stopping with the outcome
+// `check` returns after one, if any.
+fn run_frames(
+    cpu: &mut Cpu,
+    frames: u64,
+    timeout: Option<Duration>,
+    mut input: impl FnMut(&mut Cpu, u64),
+    mut check: impl FnMut(&Cpu, u64) -> Option<Outcome>,
+) -> (Outcome, u64) {
+    let start = Instant::now();
+    for frame in 0..frames {
+        input(cpu, frame);
+        if let Err(fault) = cpu.run_frame() {
+            return (Outcome::Fault(fault), frame);
+        }
+        if let Some(outcome) = check(cpu, frame) {
+            return (outcome, frame + 1);
+        }
+        if cpu.is_halted() {
+            return (Outcome::Exited, frame + 1);
+        }
+        if timeout.is_some_and(|t| start.elapsed() > t) {
+            return (Outcome::Timeout, frame + 1);
+        }
+    }
+    (Outcome::Completed, frames)
+}
+
+/// The screen as text, one character per pixel: `.` for off, `#` for plane
+/// 1, `+` for plane 2 and `@` for both.
+pub fn ascii_screen(cpu: &Cpu) -> String {
+    const CHARS: [char; 4] = ['.', '#', '+', '@'];
+    let mut out = String::with_capacity((cpu.width() + 1) * cpu.height());
+    for row in cpu.gfx[..cpu.height()].iter() {
+        out.extend(
+            row[..cpu.width()]
+                .iter()
+                .map(|&p| CHARS[(p & 0x3) as usize]),
+        );
+        out.push('\n');
+    }
+    out
+}
+
+/// Writes the screen to `path` as a PNG, one image pixel per CHIP-8 pixel,
+/// coloured with `palette`.
+pub fn write_png<P: AsRef<Path>>(cpu: &Cpu, palette: &Palette, path: P) -> io::Result<()> {
+    let (width, height) = (cpu.width(), cpu.height());
+    let mut data = Vec::with_capacity(width * height * 3);
+    for row in cpu.gfx[..height].iter() {
+        for &p in row[..width].iter() {
+            let (r, g, b) = palette.color(p);
+            data.extend_from_slice(&[r, g, b]);
+        }
+    }
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let to_io = |e: png::EncodingError| io::Error::other(e);
+    let mut writer = encoder.write_header().map_err(to_io)?;
+    writer.write_image_data(&data).map_err(to_io)
+}
+
+#[cfg(test)]
+#[path = "./headless_tests.rs"]
+mod headless_tests;
@@ -0,0 +1,81 @@
+use super::*;
+use crate::cpu::Platform;
+use crate::quirks::Quirks;
+
+#[test]
+fn test_parse_script() {
+    let events = parse_script("10 release a\n\n2 press A  # fire\n10 press 3\n").unwrap();
+    assert_eq!(
+        events,
+        vec![
+            KeyEvent {
+                frame: 2,
+                key: 0xA,
+                pressed: true
+            },
+            KeyEvent {
+                frame: 10,
+                key: 0xA,
+                pressed: false
+            },
+            KeyEvent {
+                frame: 10,
+                key: 0x3,
+                pressed: true
+            },
+        ]
+    );
+    assert_eq!(
+        parse_script("1 press 1\nx press 1").unwrap_err(),
+        "line 2: \"x\" isn't a frame number"
+    );
+    assert!(parse_script("1 hold 1").is_err());
+    assert!(parse_script("1 press 10").is_err());
+    assert!(parse_script("1 press").is_err());
+}
+
+#[test]
+fn test_run_with_script() {
+    // F00A 00FD: wait for a key then exit
+    let mut cpu = Cpu::initialize(&[0xF0, 0x0A, 0x00, 0xFD], Quirks::default());
+    cpu.set_platform(Platform::SuperChip);
+    let script = parse_script("5 press 7\n6 release 7").unwrap();
+    assert_eq!(run(&mut cpu, 100, &script, None), (Outcome::Exited, 7));
+    assert_eq!(cpu.v[0], 7);
+
+    let mut cpu = Cpu::initialize(&[0xF0, 0x0A, 0x00, 0xFD], Quirks::default());
+    assert_eq!(run(&mut cpu, 100, &[], None), (Outcome::Completed, 100));
+}
+
+#[test]
+fn test_run_fault_and_timeout() {
+    let mut cpu = Cpu::initialize(&[0x12, 0x02, 0xFF, 0xFF], Quirks::default());
+    let fault = Fault::InvalidOpcode {
+        pc: 0x202,
+        opcode: 0xFFFF,
+    };
+    assert_eq!(run(&mut cpu, 10, &[], None), (Outcome::Fault(fault), 0));
+
+    let mut cpu = Cpu::initialize(&[0x12, 0x00], Quirks::default());
+    let outcome = run(&mut cpu, u64::MAX, &[], Some(Duration::from_millis(10)));
+    assert_eq!(outcome.0, Outcome::Timeout);
+}
+
+#[test]
+fn test_screenshots() {
+    // Draws the 0 glyph at (0, 0)
+    let mut cpu = Cpu::initialize(&[0xD0, 0x05], Quirks::default());
+    cpu.emulate_cycle().unwrap();
+    let ascii = ascii_screen(&cpu);
+    let lines: Vec<&str> = ascii.lines().collect();
+    assert_eq!(lines.len(), 32);
+    assert_eq!(&lines[0][..8], "####....");
+    assert_eq!(&lines[1][..8], "#..#....");
+    assert_eq!(lines[0].len(), 64);
+
+    let path = std::env::temp_dir().join("chip8emu_headless_test.png");
+    write_png(&cpu, &Palette::CLASSIC, &path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(&bytes[1..4], b"PNG");
+}
@@ -0,0 +1,317 @@
+use std::error;
+use std::fmt;
+
+/// A decoded CHIP-8 instruction.
+///
+/// `x` and `y` are register indices (0x0 - 0xF), `nn` an 8-bit immediate,
+/// `n` a 4-bit immediate and addresses are 12-bit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00E0: clears the screen.
+    Cls,
+    /// 00EE: returns from a subroutine.
+    Ret,
+    /// 00CN: scrolls the screen down N pixels (SCHIP).
+    Scd(u8),
+    /// 00DN: scrolls the screen up N pixels (XO-CHIP).
+    Scu(u8),
+    /// 00FB: scrolls the screen right 4 pixels (SCHIP).
+    Scr,
+    /// 00FC: scrolls the screen left 4 pixels (SCHIP).
+    Scl,
+    /// 00FD: exits the interpreter (SCHIP).
+    Exit,
+    /// 00FE: switches to 64x32 low resolution (SCHIP).
+    Low,
+    /// 00FF: switches to 128x64 high resolution (SCHIP).
+    High,
+    /// 1NNN: jumps to NNN.
+    Jp(u16),
+    /// 2NNN: calls the subroutine at NNN.
+    Call(u16),
+    /// 3XNN: skips the next instruction if VX equals NN.
+    SeByte { x: usize, nn: u8 },
+    /// 4XNN: skips the next instruction if VX doesn't equal NN.
+    SneByte { x: usize, nn: u8 },
+    /// 5XY0: skips the next instruction if VX equals VY.
+    SeReg { x: usize, y: usize },
+    /// 5XY2: stores VX to VY in memory starting at I, leaving I alone (XO-CHIP).
+    SaveRange { x: usize, y: usize },
+    /// 5XY3: fills VX to VY from memory starting at I, leaving I alone (XO-CHIP).
+    LoadRange { x: usize, y: usize },
+    /// 6XNN: sets VX to NN.
+    LdByte { x: usize, nn: u8 },
+    /// 7XNN: adds NN to VX, without touching the carry flag.
+    AddByte { x: usize, nn: u8 },
+    /// 8XY0: sets VX to VY.
+    LdReg { x: usize, y: usize },
+    /// 8XY1: sets VX to VX or VY.
+    Or { x: usize, y: usize },
+    /// 8XY2: sets VX to VX and VY.
+    And { x: usize, y: usize },
+    /// 8XY3: sets VX to VX xor VY.
+    Xor { x: usize, y: usize },
+    /// 8XY4: adds VY to VX, VF is the carry.
+    AddReg { x: usize, y: usize },
+    /// 8XY5: subtracts VY from VX, VF is set when there is no borrow.
+    Sub { x: usize, y: usize },
+    /// 8XY6: shifts VX right by one, VF is the bit shifted out.
+    Shr { x: usize, y: usize },
+    /// 8XY7: sets VX to VY minus VX, VF is set when there is no borrow.
+    Subn { x: usize, y: usize },
+    /// 8XYE: shifts VX left by one, VF is the bit shifted out.
+    Shl { x: usize, y: usize },
+    /// 9XY0: skips the next instruction if VX doesn't equal VY.
+    SneReg { x: usize, y: usize },
+    /// ANNN: sets I to NNN.
+    LdI(u16),
+    /// BNNN: jumps to NNN plus V0.
+    JpV0(u16),
+    /// CXNN: sets VX to a random byte and NN.
+    Rnd { x: usize, nn: u8 },
+    /// DXYN: draws an N rows tall sprite from I at (VX, VY). On SCHIP an N
+    /// of 0 draws a 16x16 sprite, on CHIP-8 it draws nothing.
+    Drw { x: usize, y: usize, n: u8 },
+    /// F000 NNNN: sets I to the 16-bit address NNNN (XO-CHIP).
+    LdILong(u16),
+    /// FN01: selects the bitplanes drawing instructions work on (XO-CHIP).
+    Plane(u8),
+    /// F002: loads the 16 byte audio pattern from memory at I (XO-CHIP).
+    Audio,
+    /// EX9E: skips the next instruction if the key in VX is pressed.
+    Skp(usize),
+    /// EXA1: skips the next instruction if the key in VX isn't pressed.
+    Sknp(usize),
+    /// FX07: sets VX to the delay timer.
+    LdVxDt(usize),
+    /// FX0A: waits for a key press and stores it in VX.
+    LdVxK(usize),
+    /// FX15: sets the delay timer to VX.
+    LdDtVx(usize),
+    /// FX18: sets the sound timer to VX.
+    LdStVx(usize),
+    /// FX1E: adds VX to I.
+    AddIVx(usize),
+    /// FX29: points I at the font glyph for the digit in VX.
+    LdFVx(usize),
+    /// FX30: points I at the big 8x10 font glyph for the digit in VX (SCHIP).
+    LdHfVx(usize),
+    /// FX3A: sets the audio pattern playback pitch to VX (XO-CHIP).
+    Pitch(usize),
+    /// FX33: stores the BCD representation of VX at I, I+1 and I+2.
+    LdBVx(usize),
+    /// FX55: stores V0 to VX in memory starting at I.
+    LdIVx(usize),
+    /// FX65: fills V0 to VX from memory starting at I.
+    LdVxI(usize),
+    /// FX75: stores V0 to VX in the RPL user flags (SCHIP).
+    LdRVx(usize),
+    /// FX85: fills V0 to VX from the RPL user flags (SCHIP).
+    LdVxR(usize),
+}
+
+impl Instruction {
+    /// Whether the instruction is a SUPER-CHIP extension rather than part of
+    /// the original CHIP-8 set.
+    pub fn is_schip(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Scd(_)
+                | Instruction::Scr
+                | Instruction::Scl
+                | Instruction::Exit
+                | Instruction::Low
+                | Instruction::High
+                | Instruction::LdHfVx(_)
+                | Instruction::LdRVx(_)
+                | Instruction::LdVxR(_)
+        )
+    }
+
+    /// Whether the instruction only exists on XO-CHIP.
+    pub fn is_xochip(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Scu(_)
+                | Instruction::SaveRange { .. }
+                | Instruction::LoadRange { .. }
+                | Instruction::LdILong(_)
+                | Instruction::Plane(_)
+                | Instruction::Audio
+                | Instruction::Pitch(_)
+        )
+    }
+
+    /// Size of the instruction in bytes: 4 for `F000 NNNN`, 2 for the rest.
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::LdILong(_) => 4,
+            _ => 2,
+        }
+    }
+}
+
+/// Prints the instruction as assembly, e.g. `LD V3, 0x10` or
+/// `DRW V0, V1, 5`. This is the syntax the disassembler emits and the
+/// assembler reads.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::Scd(n) => write!(f, "SCD {}", n),
+            Instruction::Scu(n) => write!(f, "SCU {}", n),
+            Instruction::Scr => write!(f, "SCR"),
+            Instruction::Scl => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::Low => write!(f, "LOW"),
+            Instruction::High => write!(f, "HIGH"),
+            Instruction::Jp(nnn) => write!(f, "JP {:#05x}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL {:#05x}", nnn),
+            Instruction::SeByte { x, nn } => write!(f, "SE V{:X}, {:#04x}", x, nn),
+            Instruction::SneByte { x, nn } => write!(f, "SNE V{:X}, {:#04x}", x, nn),
+            Instruction::SeReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange { x, y } => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            Instruction::LoadRange { x, y } => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            Instruction::LdByte { x, nn } => write!(f, "LD V{:X}, {:#04x}", x, nn),
+            Instruction::AddByte { x, nn } => write!(f, "ADD V{:X}, {:#04x}", x, nn),
+            Instruction::LdReg { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddReg { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::Shr { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::Subn { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::Shl { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SneReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LdI(nnn) => write!(f, "LD I, {:#05x}", nnn),
+            Instruction::JpV0(nnn) => write!(f, "JP V0, {:#05x}", nnn),
+            Instruction::Rnd { x, nn } => write!(f, "RND V{:X}, {:#04x}", x, nn),
+            Instruction::Drw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::LdILong(nnnn) => write!(f, "LD I, LONG {:#06x}", nnnn),
+            Instruction::Plane(n) => write!(f, "PLANE {}", n),
+            Instruction::Audio => write!(f, "AUDIO"),
+            Instruction::Skp(x) => write!(f, "SKP V{:X}", x),
+            Instruction::Sknp(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LdVxDt(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::LdVxK(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::LdDtVx(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::LdStVx(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIVx(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LdFVx(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LdHfVx(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Instruction::LdBVx(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::LdIVx(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LdVxI(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::LdRVx(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LdVxR(x) => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+/// Returned by [`decode`] for a word that isn't a valid instruction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode {:#06x}", self.opcode)
+    }
+}
+
+impl error::Error for DecodeError {}
+
+/// Like [`decode`], but also knows about the 4 byte XO-CHIP `F000 NNNN`,
+/// which takes its address from `next`, the word following `opcode`.
+pub fn decode_long(opcode: u16, next: u16) -> Result<Instruction, DecodeError> {
+    if opcode == 0xF000 {
+        return Ok(Instruction::LdILong(next));
+    }
+    decode(opcode)
+}
+
+/// Turns a big-endian opcode word into an [`Instruction`].
+///
+/// `F000` needs the word after it and is only understood by [`decode_long`].
+pub fn decode(opcode: u16) -> Result<Instruction, DecodeError> {
+    let nnn: u16 = opcode & 0x0FFF;
+    let nn: u8 = (opcode & 0x00FF) as u8;
+    let n: u8 = (opcode & 0x000F) as u8;
+    let x: usize = ((opcode & 0x0F00) >> 8) as usize;
+    let y: usize = ((opcode & 0x00F0) >> 4) as usize;
+
+    // All opcodes use the first 4 bits to specify what command
+    // it is. The remaining 12 bits are arguments.
+    let ins = match opcode & 0xF000 {
+        0x0000 => match nnn {
+            0x0E0 => Instruction::Cls,
+            0x0EE => Instruction::Ret,
+            0x0FB => Instruction::Scr,
+            0x0FC => Instruction::Scl,
+            0x0FD => Instruction::Exit,
+            0x0FE => Instruction::Low,
+            0x0FF => Instruction::High,
+            _ if nnn & 0xFF0 == 0x0C0 => Instruction::Scd(n),
+            _ if nnn & 0xFF0 == 0x0D0 => Instruction::Scu(n),
+            _ => return Err(DecodeError { opcode }),
+        },
+        0x1000 => Instruction::Jp(nnn),
+        0x2000 => Instruction::Call(nnn),
+        0x3000 => Instruction::SeByte { x, nn },
+        0x4000 => Instruction::SneByte { x, nn },
+        0x5000 => match n {
+            0x0 => Instruction::SeReg { x, y },
+            0x2 => Instruction::SaveRange { x, y },
+            0x3 => Instruction::LoadRange { x, y },
+            _ => return Err(DecodeError { opcode }),
+        },
+        0x6000 => Instruction::LdByte { x, nn },
+        0x7000 => Instruction::AddByte { x, nn },
+        0x8000 => match n {
+            0x0 => Instruction::LdReg { x, y },
+            0x1 => Instruction::Or { x, y },
+            0x2 => Instruction::And { x, y },
+            0x3 => Instruction::Xor { x, y },
+            0x4 => Instruction::AddReg { x, y },
+            0x5 => Instruction::Sub { x, y },
+            0x6 => Instruction::Shr { x, y },
+            0x7 => Instruction::Subn { x, y },
+            0xE => Instruction::Shl { x, y },
+            _ => return Err(DecodeError { opcode }),
+        },
+        0x9000 if n == 0 => Instruction::SneReg { x, y },
+        0xA000 => Instruction::LdI(nnn),
+        0xB000 => Instruction::JpV0(nnn),
+        0xC000 => Instruction::Rnd { x, nn },
+        0xD000 => Instruction::Drw { x, y, n },
+        0xE000 => match nn {
+            0x9E => Instruction::Skp(x),
+            0xA1 => Instruction::Sknp(x),
+            _ => return Err(DecodeError { opcode }),
+        },
+        0xF000 => match nn {
+            0x01 => Instruction::Plane(x as u8),
+            0x02 if x == 0 => Instruction::Audio,
+            0x07 => Instruction::LdVxDt(x),
+            0x0A => Instruction::LdVxK(x),
+            0x15 => Instruction::LdDtVx(x),
+            0x18 => Instruction::LdStVx(x),
+            0x1E => Instruction::AddIVx(x),
+            0x29 => Instruction::LdFVx(x),
+            0x30 => Instruction::LdHfVx(x),
+            0x3A => Instruction::Pitch(x),
+            0x33 => Instruction::LdBVx(x),
+            0x55 => Instruction::LdIVx(x),
+            0x65 => Instruction::LdVxI(x),
+            0x75 => Instruction::LdRVx(x),
+            0x85 => Instruction::LdVxR(x),
+            _ => return Err(DecodeError { opcode }),
+        },
+        _ => return Err(DecodeError { opcode }),
+    };
+    Ok(ins)
+}
@@ -0,0 +1,497 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Why a key configuration couldn't be read.
+#[derive(Debug)]
+pub enum KeyMapError {
+    Io(io::Error),
+    /// The file isn't TOML in the configuration's shape.
+    Toml(toml::de::Error),
+    /// A binding is out of range, e.g. an unknown preset or keypad key.
+    Invalid(String),
+}
+
+impl fmt::Display for KeyMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyMapError::Io(e) => write!(f, "{}", e),
+            KeyMapError::Toml(e) => write!(f, "{}", e),
+            KeyMapError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl error::Error for KeyMapError {}
+
+impl From<io::Error> for KeyMapError {
+    fn from(e: io::Error) -> KeyMapError {
+        KeyMapError::Io(e)
+    }
+}
+
+/// Something the emulator itself does when a key is pressed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    Quit,
+    Pause,
+    Reset,
+    SaveState,
+    LoadState,
+    PrevSlot,
+    NextSlot,
+    Faster,
+    Slower,
+    /// Held down rather than pressed: plays the game backwards.
+    Rewind,
+    Debug,
+    /// Switches to the next colour theme.
+    Palette,
+    Fullscreen,
+    /// Turns snapping the screen to whole number scales on or off.
+    IntegerScale,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::Quit,
+        Action::Pause,
+        Action::Reset,
+        Action::SaveState,
+        Action::LoadState,
+        Action::PrevSlot,
+        Action::NextSlot,
+        Action::Faster,
+        Action::Slower,
+        Action::Rewind,
+        Action::Debug,
+        Action::Palette,
+        Action::Fullscreen,
+        Action::IntegerScale,
+    ];
+
+    // The key it's on unless the configuration says otherwise
+    fn default_key(self) -> &'static str {
+        match self {
+            Action::Quit => "Escape",
+            Action::Pause => "P",
+            Action::Reset => "F2",
+            Action::SaveState => "F5",
+            Action::LoadState => "F9",
+            Action::PrevSlot => "F6",
+            Action::NextSlot => "F7",
+            Action::Faster => "=",
+            Action::Slower => "-",
+            Action::Rewind => "Backspace",
+            Action::Debug => "F12",
+            Action::Palette => "F3",
+            Action::Fullscreen => "F11",
+            Action::IntegerScale => "F4",
+        }
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Action, String> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|a| a.to_string() == s)
+            .ok_or_else(|| format!("unknown action {:?}", s))
+    }
+}
+
+impl fmt::Display for Action {
+    /// The name used in the configuration file.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Action::Quit => "quit",
+            Action::Pause => "pause",
+            Action::Reset => "reset",
+            Action::SaveState => "save_state",
+            Action::LoadState => "load_state",
+            Action::PrevSlot => "prev_slot",
+            Action::NextSlot => "next_slot",
+            Action::Faster => "faster",
+            Action::Slower => "slower",
+            Action::Rewind => "rewind",
+            Action::Debug => "debug",
+            Action::Palette => "palette",
+            Action::Fullscreen => "fullscreen",
+            Action::IntegerScale => "integer_scale",
+        })
+    }
+}
+
+/// Layouts to start from, by name. Keys are named by their place on a US
+/// keyboard, so `default` is the same four rows on AZERTY or QWERTZ.
+pub const PRESETS: [&str; 3] = ["default", "hex", "numpad"];
+
+// The keys of each preset, row by row as the keypad is laid out:
+// 1 2 3 C / 4 5 6 D / 7 8 9 E / A 0 B F
+const KEYPAD_ORDER: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+const DEFAULT_KEYS: [&str; 16] = [
+    "1", "2", "3", "4", "Q", "W", "E", "R", "A", "S", "D", "F", "Z", "X", "C", "V",
+];
+const HEX_KEYS: [&str; 16] = [
+    "1", "2", "3", "C", "4", "5", "6", "D", "7", "8", "9", "E", "A", "0", "B", "F",
+];
+const NUMPAD_KEYS: [&str; 16] = [
+    "Keypad 7",
+    "Keypad 8",
+    "Keypad 9",
+    "Keypad /",
+    "Keypad 4",
+    "Keypad 5",
+    "Keypad 6",
+    "Keypad *",
+    "Keypad 1",
+    "Keypad 2",
+    "Keypad 3",
+    "Keypad -",
+    "Keypad 0",
+    "Keypad .",
+    "Keypad Enter",
+    "Keypad +",
+];
+
+/// Game controller buttons, by their SDL names.
+pub const CONTROLLER_BUTTONS: [&str; 15] = [
+    "a",
+    "b",
+    "x",
+    "y",
+    "back",
+    "guide",
+    "start",
+    "leftstick",
+    "rightstick",
+    "leftshoulder",
+    "rightshoulder",
+    "dpup",
+    "dpdown",
+    "dpleft",
+    "dpright",
+];
+
+/// Game controller axes, by their SDL names. Sticks are followed by the
+/// direction that presses the key, `-` being left or up; triggers press it
+/// when pulled.
+pub const CONTROLLER_AXES: [&str; 10] = [
+    "leftx-",
+    "leftx+",
+    "lefty-",
+    "lefty+",
+    "rightx-",
+    "rightx+",
+    "righty-",
+    "righty+",
+    "lefttrigger",
+    "righttrigger",
+];
+
+// The d-pad and left stick on 2/4/6/8, the usual directions, and the face
+// buttons on keys games often use to fire or start
+const CONTROLLER_DEFAULT: [(u8, &[&str]); 9] = [
+    (0x2, &["dpup", "lefty-"]),
+    (0x8, &["dpdown", "lefty+"]),
+    (0x4, &["dpleft", "leftx-"]),
+    (0x6, &["dpright", "leftx+"]),
+    (0x5, &["a"]),
+    (0x0, &["b"]),
+    (0xA, &["x"]),
+    (0xB, &["y"]),
+    (0xF, &["start"]),
+];
+
+/// Which keyboard keys press which keypad keys and trigger which
+/// [`Action`]s, and which controller inputs press which keypad keys. Keys
+/// are SDL scancode names, like `Q`, `Keypad 7` or `F5`, and controller
+/// inputs are in [`CONTROLLER_BUTTONS`] or [`CONTROLLER_AXES`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyMap {
+    keypad: BTreeMap<u8, Vec<String>>,
+    hotkeys: BTreeMap<Action, Vec<String>>,
+    controller: BTreeMap<u8, Vec<String>>,
+}
+
+impl KeyMap {
+    /// The layout called `name` in [`PRESETS`], with the default hotkeys.
+    pub fn preset(name: &str) -> Result<KeyMap, String> {
+        let keys = match name {
+            "default" => DEFAULT_KEYS,
+            "hex" => HEX_KEYS,
+            "numpad" => NUMPAD_KEYS,
+            _ => {
+                return Err(format!(
+                    "unknown key preset {:?}, try {}",
+                    name,
+                    PRESETS.join(", ")
+                ))
+            }
+        };
+        Ok(KeyMap {
+            keypad: KEYPAD_ORDER
+                .iter()
+                .zip(keys.iter())
+                .map(|(&k, &name)| (k, vec![name.to_string()]))
+                .collect(),
+            hotkeys: Action::ALL
+                .iter()
+                .map(|&a| (a, vec![a.default_key().to_string()]))
+                .collect(),
+            controller: CONTROLLER_DEFAULT
+                .iter()
+                .map(|&(k, inputs)| (k, inputs.iter().map(|i| i.to_string()).collect()))
+                .collect(),
+        })
+    }
+
+    /// Every key bound to a keypad key.
+    pub fn keypad(&self) -> impl Iterator<Item = (&str, u8)> {
+        self.keypad
+            .iter()
+            .flat_map(|(&k, names)| names.iter().map(move |n| (n.as_str(), k)))
+    }
+
+    /// Every key bound to an action.
+    pub fn hotkeys(&self) -> impl Iterator<Item = (&str, Action)> {
+        self.hotkeys
+            .iter()
+            .flat_map(|(&a, names)| names.iter().map(move |n| (n.as_str(), a)))
+    }
+
+    /// Every controller input bound to a keypad key.
+    pub fn controller(&self) -> impl Iterator<Item = (&str, u8)> {
+        self.controller
+            .iter()
+            .flat_map(|(&k, names)| names.iter().map(move |n| (n.as_str(), k)))
+    }
+
+    // Rebinds the keypad keys and actions `layer` names, taking the keys and
+    // inputs it uses away from whatever they did before
+    fn apply(&mut self, layer: &Layer) {
+        let taken = |name: &String| layer.names().any(|n| n.eq_ignore_ascii_case(name));
+        for keys in self.keypad.values_mut() {
+            keys.retain(|n| !taken(n));
+        }
+        for keys in self.hotkeys.values_mut() {
+            keys.retain(|n| !taken(n));
+        }
+        for inputs in self.controller.values_mut() {
+            inputs.retain(|n| !layer.controller.values().flatten().any(|i| i == n));
+        }
+        for (&k, keys) in &layer.keypad {
+            self.keypad.insert(k, keys.clone());
+        }
+        for (&a, keys) in &layer.hotkeys {
+            self.hotkeys.insert(a, keys.clone());
+        }
+        for (&k, inputs) in &layer.controller {
+            self.controller.insert(k, inputs.clone());
+        }
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> KeyMap {
+        KeyMap::preset("default").unwrap()
+    }
+}
+
+/// The key configuration file: a preset and bindings over it, and more of
+/// both for particular ROMs, keyed by SHA-1.
+///
+/// ```toml
+/// preset = "default"           # default, hex or numpad
+///
+/// [keypad]                     # keypad key = keyboard key(s)
+/// 0 = ["X", "Space"]
+///
+/// [hotkeys]                    # action = keyboard key(s)
+/// pause = "Return"
+///
+/// [controller]                 # keypad key = controller input(s)
+/// 5 = ["a", "righttrigger"]
+///
+/// [rom.b232ef880bd6060fb45fa6effed7edf0ae95670e]
+/// keypad = { 1 = "W", 4 = "S", C = "Up", D = "Down" }
+/// controller = { 1 = "dpup", 4 = "dpdown", C = "y", D = "a" }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct KeyConfig {
+    preset: Option<String>,
+    global: Layer,
+    roms: BTreeMap<String, (Option<String>, Layer)>,
+}
+
+impl KeyConfig {
+    /// Reads a configuration file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<KeyConfig, KeyMapError> {
+        KeyConfig::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Parses a configuration from TOML.
+    pub fn from_toml(text: &str) -> Result<KeyConfig, KeyMapError> {
+        let file: File = toml::from_str(text).map_err(KeyMapError::Toml)?;
+        let mut roms = BTreeMap::new();
+        for (sha1, rom) in file.rom {
+            let invalid = |msg: String| KeyMapError::Invalid(format!("rom {}: {}", sha1, msg));
+            if sha1.len() != 40 || !sha1.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid("isn't a SHA-1".to_string()));
+            }
+            let layer = Layer::new(rom.keypad, rom.hotkeys, rom.controller).map_err(invalid)?;
+            check_preset(&rom.preset).map_err(invalid)?;
+            roms.insert(sha1.to_ascii_lowercase(), (rom.preset, layer));
+        }
+        check_preset(&file.preset).map_err(KeyMapError::Invalid)?;
+        Ok(KeyConfig {
+            preset: file.preset,
+            global: Layer::new(file.keypad, file.hotkeys, file.controller)
+                .map_err(KeyMapError::Invalid)?,
+            roms,
+        })
+    }
+
+    /// The key map for the ROM with this SHA-1, in hex. `preset` takes the
+    /// place of the ones in the file; the bindings still apply over it.
+    pub fn keymap(&self, sha1: &str, preset: Option<&str>) -> Result<KeyMap, String> {
+        let rom = self.roms.get(&sha1.to_ascii_lowercase());
+        let name = preset
+            .or_else(|| rom.and_then(|(p, _)| p.as_deref()))
+            .or(self.preset.as_deref())
+            .unwrap_or("default");
+        let mut map = KeyMap::preset(name)?;
+        map.apply(&self.global);
+        if let Some((_, layer)) = rom {
+            map.apply(layer);
+        }
+        Ok(map)
+    }
+}
+
+fn check_preset(preset: &Option<String>) -> Result<(), String> {
+    match preset {
+        Some(name) => KeyMap::preset(name).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+// Bindings replacing those of a preset
+#[derive(Clone, Debug, Default)]
+struct Layer {
+    keypad: BTreeMap<u8, Vec<String>>,
+    hotkeys: BTreeMap<Action, Vec<String>>,
+    controller: BTreeMap<u8, Vec<String>>,
+}
+
+impl Layer {
+    fn new(
+        keypad: BTreeMap<String, Keys>,
+        hotkeys: BTreeMap<String, Keys>,
+        controller: BTreeMap<String, Keys>,
+    ) -> Result<Layer, String> {
+        let mut layer = Layer::default();
+        for (key, keys) in keypad {
+            layer.keypad.insert(keypad_key(&key)?, keys.into_vec());
+        }
+        for (action, keys) in hotkeys {
+            layer.hotkeys.insert(action.parse()?, keys.into_vec());
+        }
+        for (key, inputs) in controller {
+            let inputs = inputs.into_vec();
+            if let Some(bad) = inputs.iter().find(|i| {
+                !CONTROLLER_BUTTONS.contains(&i.as_str()) && !CONTROLLER_AXES.contains(&i.as_str())
+            }) {
+                return Err(format!("{:?} isn't a controller button or axis", bad));
+            }
+            layer.controller.insert(keypad_key(&key)?, inputs);
+        }
+        // A key or input doing two things at once is a mistake
+        let mut seen: Vec<&str> = Vec::new();
+        for name in layer.names() {
+            if seen.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                return Err(format!("{:?} is bound twice", name));
+            }
+            seen.push(name);
+        }
+        let mut seen: Vec<&String> = Vec::new();
+        for input in layer.controller.values().flatten() {
+            if seen.contains(&input) {
+                return Err(format!("{:?} is bound twice", input));
+            }
+            seen.push(input);
+        }
+        Ok(layer)
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        let keypad = self.keypad.values().flatten();
+        let hotkeys = self.hotkeys.values().flatten();
+        keypad.chain(hotkeys).map(String::as_str)
+    }
+}
+
+fn keypad_key(key: &str) -> Result<u8, String> {
+    match u8::from_str_radix(key, 16) {
+        Ok(k) if key.len() == 1 => Ok(k),
+        _ => Err(format!("{:?} isn't a keypad key, 0 to F", key)),
+    }
+}
+
+// The TOML layout, turned into `Layer`s once read.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    preset: Option<String>,
+    #[serde(default)]
+    keypad: BTreeMap<String, Keys>,
+    #[serde(default)]
+    hotkeys: BTreeMap<String, Keys>,
+    #[serde(default)]
+    controller: BTreeMap<String, Keys>,
+    #[serde(default)]
+    rom: BTreeMap<String, RomKeys>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RomKeys {
+    preset: Option<String>,
+    #[serde(default)]
+    keypad: BTreeMap<String, Keys>,
+    #[serde(default)]
+    hotkeys: BTreeMap<String, Keys>,
+    #[serde(default)]
+    controller: BTreeMap<String, Keys>,
+}
+
+// One key name or several
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Keys {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Keys::One(name) => vec![name],
+            Keys::Many(names) => names,
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./keymap_tests.rs"]
+mod keymap_tests;
@@ -0,0 +1,164 @@
+use super::*;
+
+const PONG: &str = "b232ef880bd6060fb45fa6effed7edf0ae95670e";
+
+fn key_of(map: &KeyMap, key: u8) -> Vec<&str> {
+    map.keypad()
+        .filter(|&(_, k)| k == key)
+        .map(|(n, _)| n)
+        .collect()
+}
+
+fn action_of(map: &KeyMap, action: Action) -> Vec<&str> {
+    map.hotkeys()
+        .filter(|&(_, a)| a == action)
+        .map(|(n, _)| n)
+        .collect()
+}
+
+#[test]
+fn test_presets() {
+    let map = KeyMap::default();
+    assert_eq!(map.keypad().count(), 16);
+    assert_eq!(key_of(&map, 0x1), ["1"]);
+    assert_eq!(key_of(&map, 0xC), ["4"]);
+    assert_eq!(key_of(&map, 0x0), ["X"]);
+    assert_eq!(key_of(&map, 0xF), ["V"]);
+    assert_eq!(action_of(&map, Action::Quit), ["Escape"]);
+    assert_eq!(map.hotkeys().count(), Action::ALL.len());
+
+    let hex = KeyMap::preset("hex").unwrap();
+    assert_eq!(key_of(&hex, 0xA), ["A"]);
+    assert_eq!(key_of(&hex, 0x0), ["0"]);
+    assert_eq!(
+        key_of(&KeyMap::preset("numpad").unwrap(), 0x5),
+        ["Keypad 5"]
+    );
+    assert!(KeyMap::preset("dvorak").is_err());
+}
+
+#[test]
+fn test_config_layers() {
+    let config = KeyConfig::from_toml(&format!(
+        r#"
+        preset = "hex"
+
+        [keypad]
+        0 = ["Space", "Return"]
+
+        [hotkeys]
+        pause = "F1"
+        save_state = ["F5", "S"]
+
+        [rom.{}]
+        keypad = {{ 1 = "W", c = "Up" }}
+        "#,
+        PONG.to_ascii_uppercase()
+    ))
+    .unwrap();
+
+    let map = config
+        .keymap("0000000000000000000000000000000000000000", None)
+        .unwrap();
+    assert_eq!(key_of(&map, 0x0), ["Space", "Return"]);
+    assert_eq!(key_of(&map, 0x1), ["1"]);
+    assert_eq!(action_of(&map, Action::Pause), ["F1"]);
+    assert_eq!(action_of(&map, Action::SaveState), ["F5", "S"]);
+
+    let pong = config.keymap(PONG, None).unwrap();
+    assert_eq!(key_of(&pong, 0x1), ["W"]);
+    assert_eq!(key_of(&pong, 0xC), ["Up"]);
+    assert_eq!(key_of(&pong, 0x0), ["Space", "Return"]);
+
+    // A preset from the command line replaces the file's
+    let cli = config.keymap(PONG, Some("default")).unwrap();
+    assert_eq!(key_of(&cli, 0xF), ["V"]);
+    assert!(config.keymap(PONG, Some("dvorak")).is_err());
+}
+
+#[test]
+fn test_rebinding_takes_keys() {
+    // W is keypad 5 in the default layout
+    let config = KeyConfig::from_toml("[keypad]\n1 = \"W\"\n[hotkeys]\nquit = \"x\"").unwrap();
+    let map = config.keymap(PONG, None).unwrap();
+    assert_eq!(key_of(&map, 0x1), ["W"]);
+    assert!(key_of(&map, 0x5).is_empty());
+    assert!(key_of(&map, 0x0).is_empty());
+    assert_eq!(action_of(&map, Action::Quit), ["x"]);
+}
+
+#[test]
+fn test_bad_config() {
+    let invalid = |text: &str| match KeyConfig::from_toml(text) {
+        Err(KeyMapError::Invalid(_)) => {}
+        other => panic!("{:?} for {}", other, text),
+    };
+    invalid("preset = \"dvorak\"");
+    invalid("[keypad]\n10 = \"Q\"");
+    invalid("[keypad]\ng = \"Q\"");
+    invalid("[keypad]\n1 = \"Q\"\n2 = \"q\"");
+    invalid("[keypad]\n1 = \"Q\"\n[hotkeys]\nquit = \"Q\"");
+    invalid("[hotkeys]\nexplode = \"Q\"");
+    invalid("[rom.1234]\npreset = \"hex\"");
+    invalid("[rom.b232ef880bd6060fb45fa6effed7edf0ae95670e]\npreset = \"dvorak\"");
+
+    let toml = |text: &str| {
+        assert!(matches!(
+            KeyConfig::from_toml(text),
+            Err(KeyMapError::Toml(_))
+        ))
+    };
+    toml("layout = \"hex\"");
+    toml("preset = ");
+    assert!(matches!(
+        KeyConfig::load("no such keys.toml"),
+        Err(KeyMapError::Io(_))
+    ));
+}
+
+#[test]
+fn test_controller() {
+    let inputs = |map: &KeyMap, key: u8| -> Vec<String> {
+        map.controller()
+            .filter(|&(_, k)| k == key)
+            .map(|(n, _)| n.to_string())
+            .collect()
+    };
+    let map = KeyMap::default();
+    assert_eq!(inputs(&map, 0x2), ["dpup", "lefty-"]);
+    assert_eq!(inputs(&map, 0x5), ["a"]);
+
+    let config = KeyConfig::from_toml(&format!(
+        "[controller]\n5 = [\"a\", \"righttrigger\"]\n\
+         [rom.{}.controller]\n1 = \"dpup\"\nC = \"y\"",
+        PONG
+    ))
+    .unwrap();
+    let map = config
+        .keymap("0000000000000000000000000000000000000000", None)
+        .unwrap();
+    assert_eq!(inputs(&map, 0x5), ["a", "righttrigger"]);
+    assert_eq!(inputs(&map, 0x2), ["dpup", "lefty-"]);
+
+    // Inputs moved to another key leave the one they were on
+    let pong = config.keymap(PONG, None).unwrap();
+    assert_eq!(inputs(&pong, 0x1), ["dpup"]);
+    assert_eq!(inputs(&pong, 0x2), ["lefty-"]);
+    assert_eq!(inputs(&pong, 0xC), ["y"]);
+    assert!(inputs(&pong, 0xB).is_empty());
+    // Keyboard keys with the same names aren't affected
+    assert_eq!(key_of(&pong, 0x0), ["X"]);
+
+    for bad in &[
+        "[controller]\n5 = \"trigger\"",
+        "[controller]\n5 = \"leftx\"",
+        "[controller]\nG = \"a\"",
+        "[controller]\n5 = \"a\"\n6 = \"a\"",
+    ] {
+        assert!(
+            matches!(KeyConfig::from_toml(bad), Err(KeyMapError::Invalid(_))),
+            "{}",
+            bad
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! A CHIP-8 interpreter core.
+//!
+//! The crate has no windowing or input dependency: load a ROM with
+//! [`Cartridge`](cartridge::Cartridge), hand its bytes to
+//! [`Cpu::initialize`](cpu::Cpu::initialize) and call
+//! [`Cpu::emulate_cycle`](cpu::Cpu::emulate_cycle) in a loop. The current
+//! screen is available in [`Cpu::gfx`](cpu::Cpu::gfx), one byte per pixel.
+//! A bad opcode stops the loop with a [`Fault`](cpu::Fault) instead of
+//! bringing the host process down.
+//!
+//! ```no_run
+//! use chip8emu::cartridge::Cartridge;
+//! use chip8emu::cpu::Cpu;
+//! use chip8emu::quirks::Quirks;
+//!
+//! let c = Cartridge::load("pong.rom").expect("couldn't load pong.rom");
+//! let mut cpu = Cpu::initialize(&c.rom, Quirks::default());
+//! cpu.set_platform(c.platform);
+//! loop {
+//!     if let Err(fault) = cpu.emulate_cycle() {
+//!         eprintln!("{}", fault);
+//!         break;
+//!     }
+//!     // draw cpu.gfx ...
+//! }
+//! ```
+//!
+//! The SDL front end in `main.rs` is just one consumer of this API.
+
+pub mod asm;
+pub mod audio;
+pub mod cartridge;
+pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+pub mod font;
+pub mod headless;
+pub mod instruction;
+pub mod keymap;
+pub mod movie;
+pub mod palette;
+pub mod quirks;
+pub mod random;
+pub mod rewind;
+pub mod romdb;
+pub mod state;
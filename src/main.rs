@@ -1,85 +1,887 @@
+#[cfg(feature = "sdl")]
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+#[cfg(feature = "sdl")]
+use sdl2::keyboard::Scancode;
+use serde::Deserialize;
 use std::env;
-use std::fs::File;
-use std::io::prelude::*;
+#[cfg(feature = "sdl")]
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "sdl")]
+use std::sync::mpsc;
+#[cfg(feature = "sdl")]
+use std::thread;
 use std::time::Duration;
+#[cfg(feature = "sdl")]
+use std::time::Instant;
 
-mod cartridge;
-mod cpu;
+#[cfg(feature = "sdl")]
+use chip8emu::audio::{Beeper, HeadlessBeeper};
+#[cfg(feature = "sdl")]
+use chip8emu::keymap::Action;
+
+use chip8emu::audio::Tone;
+use chip8emu::debugger::Debugger;
+use chip8emu::keymap::{KeyConfig, KeyMap};
+use chip8emu::movie::{Movie, Player};
+use chip8emu::palette::Palette;
+use chip8emu::quirks::Quirks;
+use chip8emu::rewind::{self, Rewind};
+use chip8emu::romdb::Database;
+use chip8emu::{asm, cartridge, cpu, disasm, headless, random};
+
+#[cfg(feature = "sdl")]
 mod display;
+#[cfg(feature = "sdl")]
+mod sdl_audio;
+#[cfg(feature = "sdl")]
+mod sdl_controller;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("disasm") => disasm(&args[1..]),
+        Some("asm") => assemble(&args[1..]),
+        Some("run") => play(&args[1..]),
+        _ => play(&args),
+    }
+}
+
+fn play(args: &[String]) {
+    let mut rom: Option<&str> = None;
+    let mut hz: Option<u32> = None;
+    let mut platform: Option<cpu::Platform> = None;
+    let mut quirks: Option<Quirks> = None;
+    let mut tone = Tone::default();
+    let mut rewind_len = rewind::DEFAULT_REWIND_LEN;
+    let mut rewind_interval = rewind::DEFAULT_REWIND_INTERVAL;
+    let mut debug = false;
+    let mut seed: Option<u64> = None;
+    let mut stack = cpu::STACK_DEPTH;
+    let mut memory = cpu::MemoryPolicy::default();
+    let mut vip_random = false;
+    let mut record: Option<String> = None;
+    let mut movie: Option<String> = None;
+    let mut romdbs: Vec<String> = Vec::new();
+    let mut keys: Option<String> = None;
+    let mut palette: Option<Palette> = None;
+    let mut window = Window::default();
+    let mut headless: Option<Headless> = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--hz" => hz = Some(parse_value(arg, it.next())),
+            "--platform" => platform = Some(parse_value(arg, it.next())),
+            "--quirks" => quirks = Some(parse_value(arg, it.next())),
+            "--beep" => tone.frequency = parse_value(arg, it.next()),
+            "--waveform" => tone.waveform = parse_value(arg, it.next()),
+            "--volume" => tone.volume = parse_value(arg, it.next()),
+            "--rewind" => rewind_len = parse_value(arg, it.next()),
+            "--rewind-interval" => rewind_interval = parse_value(arg, it.next()),
+            "--debug" => debug = true,
+            "--stack" => stack = parse_value(arg, it.next()),
+            "--memory" => memory = parse_value(arg, it.next()),
+            "--seed" => seed = Some(parse_value(arg, it.next())),
+            "--vip-random" => vip_random = true,
+            "--record" => record = Some(parse_value(arg, it.next())),
+            "--movie" => movie = Some(parse_value(arg, it.next())),
+            "--romdb" => romdbs.push(parse_value(arg, it.next())),
+            "--keys" => keys = Some(parse_value(arg, it.next())),
+            "--palette" => palette = Some(parse_value(arg, it.next())),
+            "--scale" => window.scale = Some(parse_value(arg, it.next())),
+            "--fullscreen" => window.fullscreen = true,
+            "--integer-scale" => window.integer_scale = true,
+            "--headless" => headless = Some(headless.unwrap_or_default()),
+            "--frames" => {
+                headless.get_or_insert_with(Default::default).frames = parse_value(arg, it.next())
+            }
+            "--input" => {
+                headless.get_or_insert_with(Default::default).input =
+                    Some(parse_value(arg, it.next()))
+            }
+            "--screenshot" => {
+                headless.get_or_insert_with(Default::default).screenshot =
+                    Some(parse_value(arg, it.next()))
+            }
+            "--dump-state" => {
+                headless.get_or_insert_with(Default::default).dump_state =
+                    Some(parse_value(arg, it.next()))
+            }
+            "--timeout" => {
+                let secs: f64 = parse_value(arg, it.next());
+                // Negative, NaN, infinite and absurdly long times don't fit
+                let timeout = Duration::try_from_secs_f64(secs).unwrap_or_else(|_| {
+                    eprintln!("--timeout needs a number of seconds, 0 or more");
+                    usage();
+                });
+                headless.get_or_insert_with(Default::default).timeout = Some(timeout)
+            }
+            _ if rom.is_none() => rom = Some(arg),
+            _ => usage(),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage());
+    if record.is_some() && (movie.is_some() || headless.is_some()) {
+        eprintln!("--record only works when playing in the window");
+        usage();
+    }
+    if debug && (record.is_some() || movie.is_some()) {
+        eprintln!("--debug can't be used while recording or playing a movie");
+        usage();
+    }
+
+    let c = load_cartridge(rom);
+    let config = load_config();
+    let db = load_database(&romdbs);
+    let info = db.lookup(&c.sha1_hex());
+
+    // Options given by the user win over the database, which wins over
+    // guesses and defaults
+    let platform = platform
+        .or_else(|| info.and_then(|i| i.platform))
+        .unwrap_or(c.platform);
+    if let Err(e) = c.check_fits(platform) {
+        eprintln!("can't load {}: {}", rom, e);
+        std::process::exit(EXIT_BAD_FILE);
+    }
+    eprintln!(
+        "{}: {} bytes, SHA-1 {}, {}",
+        c.name,
+        c.size(),
+        c.sha1_hex(),
+        platform
+    );
+    if let Some(info) = info {
+        eprintln!("{}", info.credits());
+        if !info.keys.is_empty() {
+            let keys: Vec<String> = info
+                .keys
+                .iter()
+                .map(|(what, key)| format!("{} {:X}", what, key))
+                .collect();
+            eprintln!("keys: {}", keys.join(", "));
+        }
+    }
+    let mut quirks = quirks
+        .or_else(|| info.and_then(|i| i.quirks))
+        .unwrap_or_else(|| Quirks::for_platform(platform));
+    quirks.vip_random |= vip_random;
+    let hz = hz
+        .or_else(|| info.and_then(|i| i.tickrate?.checked_mul(cpu::TIMER_HZ)))
+        .unwrap_or(cpu::DEFAULT_CLOCK_HZ);
+    let palette = palette
+        .or_else(|| info.and_then(|i| i.palette))
+        .or(config.palette)
+        .unwrap_or_default();
+    let title = match info {
+        Some(info) => format!("{} - chip8emu", info.credits()),
+        None => "chip8emu by glodi".to_string(),
+    };
+    let mut cpu = cpu::Cpu::initialize(&c.rom, quirks);
+    cpu.set_clock_hz(hz);
+    cpu.set_platform(platform);
+    cpu.set_stack_depth(stack);
+    cpu.set_memory_policy(memory);
+
+    // Always seed explicitly, so a recording knows the seed it ran with
+    let seed = seed.unwrap_or_else(random::entropy_seed);
+    cpu.seed_rng(seed);
+
+    let movie = movie.map(|path| {
+        let movie = Movie::load(&path).unwrap_or_else(|e| {
+            eprintln!("couldn't read movie {}: {}", path, e);
+            std::process::exit(EXIT_BAD_FILE);
+        });
+        if let Err(e) = movie.prepare(&mut cpu) {
+            eprintln!("can't play {}: {}", path, e);
+            std::process::exit(EXIT_BAD_FILE);
+        }
+        movie
+    });
+
+    if let Some(options) = headless {
+        run_headless(cpu, options, movie, &palette);
+    }
+
+    let keys = load_keys(&c.sha1_hex(), keys.as_deref());
+    window.scale = window.scale.or(config.scale);
+    window.fullscreen |= config.fullscreen;
+    window.integer_scale |= config.integer_scale;
+    if window.scale.is_some_and(|s| !(1..=MAX_SCALE).contains(&s)) {
+        eprintln!("the scale has to be between 1 and {}", MAX_SCALE);
+        usage();
+    }
+
+    let front = Frontend {
+        rom,
+        title,
+        keys,
+        palette,
+        window,
+        tone,
+        rewind: Rewind::new(rewind_len, rewind_interval),
+        debugger: if debug { Some(Debugger::new()) } else { None },
+        recording: record.map(|path| (path, Movie::new(&cpu, seed))),
+        player: movie.map(Player::new),
+    };
+    run(cpu, front);
+}
+
+// Everything the SDL loop needs besides the machine itself
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+struct Frontend<'a> {
+    rom: &'a str,
+    title: String,
+    keys: KeyMap,
+    palette: Palette,
+    window: Window,
+    tone: Tone,
+    rewind: Rewind,
+    debugger: Option<Debugger>,
+    recording: Option<(String, Movie)>,
+    player: Option<Player>,
+}
+
+// How the window is shown
+#[derive(Default)]
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+struct Window {
+    scale: Option<u32>,
+    fullscreen: bool,
+    integer_scale: bool,
+}
+
+// Window pixels per CHIP-8 pixel, unless --scale says otherwise
+#[cfg(feature = "sdl")]
+const DEFAULT_SCALE: u32 = 10;
+
+// Past this the window is bigger than any screen it could be shown on
+const MAX_SCALE: u32 = 50;
+
+// Settings of a run without a window, all of them implying --headless
+struct Headless {
+    frames: u64,
+    input: Option<String>,
+    screenshot: Option<String>,
+    dump_state: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl Default for Headless {
+    fn default() -> Headless {
+        Headless {
+            frames: 600,
+            input: None,
+            screenshot: None,
+            dump_state: None,
+            timeout: None,
+        }
+    }
+}
+
+// Exit codes of a headless run, 2 being bad arguments or files like usage()
+const EXIT_FAULT: i32 = 1;
+const EXIT_BAD_FILE: i32 = 2;
+const EXIT_TIMEOUT: i32 = 3;
+const EXIT_DIVERGED: i32 = 4;
+
+fn run_headless(
+    mut cpu: cpu::Cpu,
+    options: Headless,
+    movie: Option<Movie>,
+    palette: &Palette,
+) -> ! {
+    let script = match &options.input {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                eprintln!("couldn't read {}: {}", path, e);
+                std::process::exit(EXIT_BAD_FILE);
+            });
+            headless::parse_script(&text).unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(EXIT_BAD_FILE);
+            })
+        }
+        None => Vec::new(),
+    };
+    let (outcome, frames) = match &movie {
+        Some(movie) => headless::replay(&mut cpu, movie, options.timeout).unwrap_or_else(|e| {
+            eprintln!("can't play the movie: {}", e);
+            std::process::exit(EXIT_BAD_FILE);
+        }),
+        None => headless::run(&mut cpu, options.frames, &script, options.timeout),
+    };
+
+    if let Some(path) = &options.screenshot {
+        let written = if path.ends_with(".png") {
+            headless::write_png(&cpu, palette, path)
+        } else {
+            std::fs::write(path, headless::ascii_screen(&cpu))
+        };
+        if let Err(e) = written {
+            eprintln!("couldn't write {}: {}", path, e);
+            std::process::exit(EXIT_BAD_FILE);
+        }
+    }
+    if let Some(path) = &options.dump_state {
+        if let Err(e) = cpu.save_state(path) {
+            eprintln!("couldn't write {}: {}", path, e);
+            std::process::exit(EXIT_BAD_FILE);
+        }
+    }
+
+    match outcome {
+        headless::Outcome::Completed | headless::Outcome::Exited => {
+            println!("ran {} frames", frames);
+            std::process::exit(0);
+        }
+        headless::Outcome::Fault(fault) => {
+            eprintln!("ERROR: {} in frame {}", fault, frames);
+            std::process::exit(EXIT_FAULT);
+        }
+        headless::Outcome::Timeout => {
+            eprintln!("timed out after {} frames", frames);
+            std::process::exit(EXIT_TIMEOUT);
+        }
+        headless::Outcome::Diverged(frame) => {
+            eprintln!("the movie went out of sync in frame {}", frame);
+            std::process::exit(EXIT_DIVERGED);
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: chip8emu [run] [options] <rom>");
+    eprintln!("       chip8emu disasm [--platform <p>] [-o <file>] <rom>");
+    eprintln!("       chip8emu asm [-o <rom>] <source>");
+    eprintln!();
+    eprintln!("  --platform <p>    chip8, schip or xochip (default guessed from the ROM)");
+    eprintln!("  --quirks <q>      default, vip, chip48, schip or xochip");
+    eprintln!("                    (default picked from the platform, vip for chip8;");
+    eprintln!("                    `default` is the emulator's historical behaviour)");
+    eprintln!("  --hz <n>          instructions per second (default 700)");
+    eprintln!("  --romdb <file>    extra ROM database, over the bundled one and");
+    eprintln!("                    roms.json in ~/.config/chip8emu");
+    eprintln!("  --palette <p>     classic, amber, lcd, high-contrast, colorblind or");
+    eprintln!("                    2 or 4 comma separated #rrggbb colours");
+    eprintln!("  --scale <n>       pixels per CHIP-8 pixel, 1 to 50 (default 10)");
+    eprintln!("  --fullscreen      start fullscreen, F11 toggles it");
+    eprintln!("  --integer-scale   only scale the screen by whole numbers");
+    eprintln!("  --keys <preset>   default, hex or numpad keyboard layout, over the");
+    eprintln!("                    one in ~/.config/chip8emu/keys.toml");
+    eprintln!("  --beep <hz>       buzzer pitch (default 440)");
+    eprintln!("  --waveform <w>    square, triangle, sawtooth or sine");
+    eprintln!("  --volume <v>      buzzer volume from 0.0 to 1.0");
+    eprintln!("  --rewind <n>      snapshots kept for rewinding, 0 disables (default 600)");
+    eprintln!("  --rewind-interval <n>");
+    eprintln!("                    frames between rewind snapshots (default 1)");
+    eprintln!("  --debug           start paused in the debugger, F12 breaks into it");
+    eprintln!("  --stack <n>       nested calls allowed, up to 64 (default 16)");
+    eprintln!("  --memory <p>      wrap, fault or ignore: what accesses past the end");
+    eprintln!("                    of memory do (default wrap)");
+    eprintln!("  --seed <n>        seed for the random numbers, for repeatable runs");
+    eprintln!("  --vip-random      make random numbers with a COSMAC VIP style routine");
+    eprintln!("  --record <file>   record the keys pressed to a movie file");
+    eprintln!("  --movie <file>    play a recorded movie back, headless or not");
+    eprintln!();
+    eprintln!("  --headless        run without a window, as fast as possible");
+    eprintln!("  --frames <n>      frames to run headless (default 600)");
+    eprintln!("  --input <file>    script of `<frame> press|release <key>` lines");
+    eprintln!("  --screenshot <f>  write the final screen, as PNG if f ends in .png");
+    eprintln!("                    or as text otherwise");
+    eprintln!("  --dump-state <f>  write the final machine state as a save state");
+    eprintln!("  --timeout <s>     give up after s seconds, exiting with 3");
+    eprintln!("                    (a --movie going out of sync exits with 4)");
+    std::process::exit(2);
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> T {
+    match value.map(|v| v.parse()) {
+        Some(Ok(v)) => v,
+        _ => {
+            eprintln!("{} is missing a valid value", flag);
+            usage();
+        }
+    }
+}
+
+// Settings in config.toml, read once at startup
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    palette: Option<Palette>,
+    scale: Option<u32>,
+    #[serde(default)]
+    fullscreen: bool,
+    #[serde(default)]
+    integer_scale: bool,
+}
+
+// config.toml in the config directory, or the defaults without one.
+fn load_config() -> Config {
+    let path = match config_dir().map(|dir| dir.join("config.toml")) {
+        Some(path) if path.exists() => path,
+        _ => return Config::default(),
+    };
+    let parsed = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| toml::from_str(&text).map_err(|e| e.to_string()));
+    parsed.unwrap_or_else(|e| {
+        eprintln!("can't read {}: {}", path.display(), e);
+        std::process::exit(EXIT_BAD_FILE);
+    })
+}
+
+// Per-user files live in $XDG_CONFIG_HOME/chip8emu, ~/.config/chip8emu by
+// default.
+fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("chip8emu"))
+}
+
+// The bundled ROM database, overridden by roms.json in the config directory
+// and then by each --romdb file in turn.
+fn load_database(extra: &[String]) -> Database {
+    let mut db = Database::bundled();
+    let user = config_dir()
+        .map(|dir| dir.join("roms.json"))
+        .filter(|path| path.exists());
+    let files = user.into_iter().chain(extra.iter().map(PathBuf::from));
+    for path in files {
+        match Database::load(&path) {
+            Ok(other) => db.merge(other),
+            Err(e) => {
+                eprintln!("can't read ROM database {}: {}", path.display(), e);
+                std::process::exit(EXIT_BAD_FILE);
+            }
+        }
+    }
+    db
+}
 
-    if args.len() != 2 {
-        println!("You need to provide the rom filename as argument!!!");
-        panic!();
+// The key map for a ROM from keys.toml in the config directory, if there is
+// one, with `preset` given on the command line.
+fn load_keys(sha1: &str, preset: Option<&str>) -> KeyMap {
+    let path = config_dir()
+        .map(|dir| dir.join("keys.toml"))
+        .filter(|path| path.exists());
+    let config = match path {
+        Some(path) => KeyConfig::load(&path).unwrap_or_else(|e| {
+            eprintln!("can't read key bindings {}: {}", path.display(), e);
+            std::process::exit(EXIT_BAD_FILE);
+        }),
+        None => KeyConfig::default(),
+    };
+    config.keymap(sha1, preset).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        usage();
+    })
+}
+
+// Reads a ROM, or exits with an error saying why it couldn't.
+fn load_cartridge(path: &str) -> cartridge::Cartridge {
+    match cartridge::Cartridge::load(path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("can't load {}: {}", path, e);
+            std::process::exit(EXIT_BAD_FILE);
+        }
     }
+}
 
-    let c = cartridge::Cartridge::new(&args[1]);
-    let mut cpu = cpu::Cpu::initialize(&c.rom);
-    let mut d = display::Display::new();
+// Writes the listing of a ROM to stdout or the -o file.
+fn disasm(args: &[String]) {
+    let mut rom: Option<&str> = None;
+    let mut platform: Option<cpu::Platform> = None;
+    let mut output: Option<&str> = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--platform" => platform = Some(parse_value(arg, it.next())),
+            "-o" | "--output" => output = Some(it.next().unwrap_or_else(|| usage())),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => usage(),
+        }
+    }
+    let rom = rom.unwrap_or_else(|| usage());
 
-    // If you want to dump it to a .txt set the parameter to true
-    // and create a file under root named instructions.txt
-    // cpu.dump_memory(false);
+    let c = load_cartridge(rom);
+    let listing = disasm::disassemble(&c.rom, platform.unwrap_or(c.platform));
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, listing) {
+                eprintln!("couldn't write {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+        None => print!("{}", listing),
+    }
+}
 
-    println!("------");
-    println!("  ");
+// Assembles a source file into a ROM next to it, or the -o file.
+fn assemble(args: &[String]) {
+    let mut source: Option<&str> = None;
+    let mut output: Option<&str> = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(it.next().unwrap_or_else(|| usage())),
+            _ if source.is_none() => source = Some(arg),
+            _ => usage(),
+        }
+    }
+    let source = source.unwrap_or_else(|| usage());
+    let output = match output {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(source).with_extension("ch8"),
+    };
+
+    let rom = asm::assemble_file(source).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if let Err(e) = std::fs::write(&output, &rom) {
+        eprintln!("couldn't write {}: {}", output.display(), e);
+        std::process::exit(1);
+    }
+    println!("wrote {} bytes to {}", rom.len(), output.display());
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run(_cpu: cpu::Cpu, _front: Frontend) {
+    eprintln!("chip8emu was built without the `sdl` feature, there is no window to play in.");
+    std::process::exit(1);
+}
+
+#[cfg(feature = "sdl")]
+fn run(mut cpu: cpu::Cpu, front: Frontend) {
+    let Frontend {
+        rom,
+        title,
+        palette,
+        window,
+        tone,
+        mut rewind,
+        mut debugger,
+        mut recording,
+        mut player,
+        keys,
+    } = front;
+    let keypad = scancodes(keys.keypad());
+    let hotkeys = scancodes(keys.hotkeys());
+    let scale = window.scale.unwrap_or(DEFAULT_SCALE);
+    let mut d =
+        display::Display::new(&title, palette, scale, window.fullscreen).unwrap_or_else(|e| {
+            eprintln!("couldn't open a window: {}", e);
+            std::process::exit(1);
+        });
+    d.integer_scale = window.integer_scale;
+    let mut beeper: Box<dyn Beeper> = match sdl_audio::SdlBeeper::new(&d.sdl_context, tone) {
+        Ok(b) => Box::new(b),
+        Err(e) => {
+            eprintln!("No audio, the buzzer will be silent: {}", e);
+            Box::new(HeadlessBeeper::new())
+        }
+    };
+    let mut controllers = sdl_controller::Controllers::new(&d.sdl_context, keys.controller())
+        .unwrap_or_else(|e| {
+            eprintln!("{} in the key bindings", e);
+            std::process::exit(EXIT_BAD_FILE);
+        });
+    let mut slot = 0;
+    let debug_input = debugger.as_ref().map(|_| {
+        println!("chip8emu debugger, `help` lists the commands and `quit` leaves");
+        stdin_lines()
+    });
+
+    if let Some((path, _)) = &recording {
+        println!("recording to {}", path);
+    }
+
+    // Reset goes back to the machine as it was before the first frame
+    let power_on = cpu;
+    let mut paused = false;
+    // When the next frame is due, so time spent emulating and drawing
+    // doesn't slow the 60 Hz down
+    let mut next_frame = Instant::now();
 
     'gameloop: loop {
-        d.canvas.clear();
+        // Anything that changes the machine behind the movie's back would
+        // make it go out of sync
+        let in_movie = recording.is_some() || player.is_some();
 
         for event in d.event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
+            controllers.handle(&event);
+            let action = match event {
+                Event::Quit { .. } => Some(Action::Quit),
+                Event::KeyDown {
+                    scancode: Some(sc),
+                    repeat,
                     ..
-                } => break 'gameloop,
+                } => hotkeys
+                    .iter()
+                    .find(|&&(s, _)| s == sc)
+                    .map(|&(_, a)| a)
+                    // Only the speed keys repeat while held down
+                    .filter(|&a| !repeat || a == Action::Faster || a == Action::Slower),
+                _ => None,
+            };
+            match action {
+                Some(Action::Quit) => break 'gameloop,
+                Some(Action::Pause) => {
+                    paused = !paused;
+                    println!("{}", if paused { "paused" } else { "resumed" });
+                }
+                Some(Action::Reset) if !in_movie => {
+                    cpu = power_on;
+                    rewind.clear();
+                    println!("reset");
+                }
+                // Speed the CPU up or down while playing
+                Some(Action::Faster) if !in_movie => change_speed(&mut cpu, true),
+                Some(Action::Slower) if !in_movie => change_speed(&mut cpu, false),
+                // Save states, in one of SAVE_SLOTS slots
+                Some(Action::SaveState) => save_slot(&cpu, rom, slot),
+                Some(Action::LoadState) if !in_movie => load_slot(&mut cpu, rom, slot),
+                Some(Action::PrevSlot) => {
+                    slot = (slot + SAVE_SLOTS - 1) % SAVE_SLOTS;
+                    println!("save slot: {}", slot);
+                }
+                Some(Action::NextSlot) => {
+                    slot = (slot + 1) % SAVE_SLOTS;
+                    println!("save slot: {}", slot);
+                }
+                Some(Action::Palette) => {
+                    d.palette = d.palette.next_theme();
+                    println!("palette: {}", d.palette.theme_name().unwrap_or("custom"));
+                }
+                Some(Action::Fullscreen) => display::toggle_fullscreen(&mut d.canvas),
+                Some(Action::IntegerScale) => {
+                    d.integer_scale = !d.integer_scale;
+                    println!(
+                        "integer scaling: {}",
+                        if d.integer_scale { "on" } else { "off" }
+                    );
+                }
+                Some(Action::Debug) => {
+                    if let Some(dbg) = debugger.as_mut() {
+                        dbg.pause();
+                        println!("paused");
+                    }
+                }
                 _ => {}
             }
         }
-        let keys: Vec<Keycode> = d
-            .event_pump
-            .keyboard_state()
-            .pressed_scancodes()
-            .filter_map(Keycode::from_scancode)
-            .collect();
-
-        let mut key_pressed: u8 = 0;
+        let pressed: Vec<Scancode> = d.event_pump.keyboard_state().pressed_scancodes().collect();
+        let held = |action: Action| {
+            hotkeys
+                .iter()
+                .any(|&(s, a)| a == action && pressed.contains(&s))
+        };
 
-        if !keys.is_empty() {
-            match keys[0] {
-                Keycode::Num1 => key_pressed = 1,
-                Keycode::Num2 => key_pressed = 2,
-                Keycode::Num3 => key_pressed = 3,
-                Keycode::Num4 => key_pressed = 4,
-                Keycode::Q => key_pressed = 5,
-                Keycode::W => key_pressed = 6,
-                Keycode::E => key_pressed = 7,
-                Keycode::R => key_pressed = 8,
-                Keycode::A => key_pressed = 9,
-                Keycode::S => key_pressed = 10,
-                Keycode::D => key_pressed = 11,
-                Keycode::F => key_pressed = 12,
-                Keycode::Z => key_pressed = 13,
-                Keycode::X => key_pressed = 14,
-                Keycode::C => key_pressed = 15,
-                Keycode::V => key_pressed = 16,
-                _ => println!("ERROR KEYCODE NOT RECOGNIZED"),
+        // A movie being played holds the keypad until it ends
+        match player.as_mut() {
+            _ if paused => {}
+            Some(p) if p.is_finished() => {
+                println!("movie finished after {} frames", p.frame());
+                player = None;
+            }
+            Some(p) => p.apply_input(&mut cpu),
+            None => {}
+        }
+        // The keyboard and controllers share the keypad
+        if player.is_none() && !paused {
+            for key in 0..16 {
+                if keypad
+                    .iter()
+                    .any(|&(s, k)| k == key && pressed.contains(&s))
+                    || controllers.is_held(key)
+                {
+                    cpu.press_key(key);
+                } else {
+                    cpu.release_key(key);
+                }
             }
         }
 
-        cpu.emulate_cycle(key_pressed);
-        cpu.print_state(key_pressed);
+        // Holding the rewind key plays the game backwards, a snapshot per frame
+        if paused {
+            // Nothing runs until the pause key is pressed again
+        } else if held(Action::Rewind) && !in_movie {
+            rewind.step_back(&mut cpu);
+        } else if let (Some(dbg), Some(input)) = (debugger.as_mut(), debug_input.as_ref()) {
+            if dbg.is_paused() && !debug_prompt(dbg, &mut cpu, &mut d, input) {
+                break 'gameloop;
+            }
+            if let Some(reason) = dbg.run_frame(&mut cpu) {
+                println!("{}", reason);
+            }
+            rewind.record(&cpu);
+        } else {
+            if let Err(e) = cpu.run_frame() {
+                eprintln!("ERROR: {}", e);
+                save_recording(&recording);
+                std::process::exit(1);
+            }
+            rewind.record(&cpu);
+            if let Some((_, movie)) = recording.as_mut() {
+                movie.record_frame(&cpu);
+            }
+            if let Some(frame) = player.as_mut().and_then(|p| p.check(&cpu)) {
+                eprintln!("the movie went out of sync in frame {}", frame);
+            }
+        }
+        beeper.set_pattern(cpu.audio_pattern());
+        beeper.frame(cpu.sound_active() && !paused);
 
-        // cpu.print_digit(15, 60, 10);
+        d.set_frame(&cpu.gfx, cpu.width(), cpu.height());
 
-        d.set_frame(&cpu.gfx);
+        // 00FD exits the interpreter, unless there's a debugger to look around in
+        if cpu.is_halted() && debugger.is_none() {
+            break 'gameloop;
+        }
 
         d.canvas.present();
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+        next_frame += FRAME;
+        let now = Instant::now();
+        if now > next_frame + FRAME * MAX_FRAMES_BEHIND {
+            // Far behind, e.g. after the debugger prompt: don't race to catch up
+            next_frame = now;
+        }
+        ::std::thread::sleep(next_frame.saturating_duration_since(now));
+    }
+    save_recording(&recording);
+}
+
+// Looks up the SDL scancodes of key names from the key map, exiting if one
+// isn't a key SDL knows.
+#[cfg(feature = "sdl")]
+fn scancodes<'a, T>(bindings: impl Iterator<Item = (&'a str, T)>) -> Vec<(Scancode, T)> {
+    bindings
+        .map(|(name, what)| match Scancode::from_name(name) {
+            Some(sc) => (sc, what),
+            None => {
+                eprintln!("unknown key {:?} in the key bindings", name);
+                std::process::exit(EXIT_BAD_FILE);
+            }
+        })
+        .collect()
+}
+
+#[cfg(feature = "sdl")]
+fn save_recording(recording: &Option<(String, Movie)>) {
+    if let Some((path, movie)) = recording {
+        match movie.save(path) {
+            Ok(()) => println!("saved a {} frame movie to {}", movie.frames, path),
+            Err(e) => eprintln!("couldn't save the movie to {}: {}", path, e),
+        }
+    }
+}
+
+// Reads debugger commands from stdin until one of them resumes the game,
+// keeping the window up to date and answering its events so it doesn't
+// look hung. Returns false to quit.
+#[cfg(feature = "sdl")]
+fn debug_prompt(
+    dbg: &mut Debugger,
+    cpu: &mut cpu::Cpu,
+    d: &mut display::Display,
+    input: &mpsc::Receiver<String>,
+) -> bool {
+    while dbg.is_paused() {
+        print!("(chip8) ");
+        let _ = io::stdout().flush();
+        let line = loop {
+            for event in d.event_pump.poll_iter() {
+                if let Event::Quit { .. } = event {
+                    return false;
+                }
+            }
+            d.set_frame(&cpu.gfx, cpu.width(), cpu.height());
+            d.canvas.present();
+            match input.recv_timeout(FRAME) {
+                Ok(line) => break line,
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return false,
+            }
+        };
+        if let "quit" | "q" = line.trim() {
+            return false;
+        }
+        match dbg.command(cpu, &line) {
+            Ok(out) if out.is_empty() => {}
+            Ok(out) => println!("{}", out),
+            Err(e) => println!("error: {}", e),
+        }
+    }
+    true
+}
+
+// Reads stdin a line at a time on a thread of its own, so waiting for a
+// debugger command doesn't hold up the window. Ends with stdin.
+#[cfg(feature = "sdl")]
+fn stdin_lines() -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+// How long a 60 Hz frame lasts
+#[cfg(feature = "sdl")]
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / cpu::TIMER_HZ as u64);
+
+// Frames the loop may fall behind before it gives up on catching up
+#[cfg(feature = "sdl")]
+const MAX_FRAMES_BEHIND: u32 = 5;
+
+#[cfg(feature = "sdl")]
+const CLOCK_STEP: u32 = 100;
+
+#[cfg(feature = "sdl")]
+fn change_speed(cpu: &mut cpu::Cpu, faster: bool) {
+    let hz = if faster {
+        cpu.clock_hz() + CLOCK_STEP
+    } else {
+        cpu.clock_hz().saturating_sub(CLOCK_STEP)
+    };
+    cpu.set_clock_hz(hz.max(CLOCK_STEP));
+    println!("clock: {} Hz", cpu.clock_hz());
+}
+
+#[cfg(feature = "sdl")]
+const SAVE_SLOTS: u32 = 10;
+
+// Slot N of pong.rom lives next to it in pong.rom.stateN
+#[cfg(feature = "sdl")]
+fn slot_path(rom: &str, slot: u32) -> String {
+    format!("{}.state{}", rom, slot)
+}
+
+#[cfg(feature = "sdl")]
+fn save_slot(cpu: &cpu::Cpu, rom: &str, slot: u32) {
+    let path = slot_path(rom, slot);
+    match cpu.save_state(&path) {
+        Ok(()) => println!("saved state to {}", path),
+        Err(e) => eprintln!("couldn't save state to {}: {}", path, e),
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn load_slot(cpu: &mut cpu::Cpu, rom: &str, slot: u32) {
+    let path = slot_path(rom, slot);
+    match cpu.load_state(&path) {
+        Ok(()) => println!("loaded state from {}", path),
+        Err(e) => eprintln!("couldn't load state from {}: {}", path, e),
     }
 }
@@ -0,0 +1,376 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::cpu::{Cpu, MemoryPolicy, Platform, STACK_DEPTH};
+use crate::headless::KeyEvent;
+use crate::quirks::{LoadStore, Quirks};
+
+/// Current version of the movie format.
+pub const MOVIE_VERSION: u32 = 1;
+
+/// Why a movie couldn't be read or played.
+#[derive(Debug)]
+pub enum MovieError {
+    Io(io::Error),
+    /// A line of the file doesn't make sense, numbered from 1.
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// The movie was recorded with another ROM.
+    RomMismatch,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::Io(e) => write!(f, "{}", e),
+            MovieError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            MovieError::RomMismatch => write!(f, "movie was recorded with a different ROM"),
+        }
+    }
+}
+
+impl error::Error for MovieError {}
+
+impl From<io::Error> for MovieError {
+    fn from(e: io::Error) -> MovieError {
+        MovieError::Io(e)
+    }
+}
+
+/// A recorded play session: the machine's configuration, every keypad
+/// change with the frame it happened before, and optionally a checksum of
+/// the machine after each frame to catch replays going out of sync.
+///
+/// The file is text, a header of `name value` lines followed by
+/// `<frame> press|release <key>` and `<frame> check <checksum>` lines.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: [u8; 20],
+    pub seed: u64,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub clock_hz: u32,
+    pub stack_depth: usize,
+    pub memory_policy: MemoryPolicy,
+    /// Frames the session lasted.
+    pub frames: u64,
+    pub events: Vec<KeyEvent>,
+    /// Checksum of the machine after each frame, by frame.
+    pub checksums: BTreeMap<u64, u64>,
+    keys: [bool; 16], // Keypad as of the last recorded frame
+}
+
+impl Movie {
+    /// Starts recording a session on `cpu`, which must be fresh from
+    /// [`Cpu::initialize`] and seeded with `seed`.
+    pub fn new(cpu: &Cpu, seed: u64) -> Movie {
+        Movie {
+            rom_hash: cpu.rom_hash(),
+            seed,
+            platform: cpu.platform(),
+            quirks: cpu.quirks(),
+            clock_hz: cpu.clock_hz(),
+            stack_depth: cpu.stack_depth(),
+            memory_policy: cpu.memory_policy(),
+            frames: 0,
+            events: Vec::new(),
+            checksums: BTreeMap::new(),
+            keys: [false; 16],
+        }
+    }
+
+    /// Records a frame `cpu` just ran: the keys that changed for it and the
+    /// machine's checksum afterwards.
+    pub fn record_frame(&mut self, cpu: &Cpu) {
+        for key in 0..16 {
+            let pressed = cpu.is_key_pressed(key);
+            if pressed != self.keys[key as usize] {
+                self.keys[key as usize] = pressed;
+                self.events.push(KeyEvent {
+                    frame: self.frames,
+                    key,
+                    pressed,
+                });
+            }
+        }
+        self.checksums.insert(self.frames, checksum(cpu));
+        self.frames += 1;
+    }
+
+    /// Configures `cpu`, fresh from [`Cpu::initialize`], the way the movie
+    /// was recorded: platform, quirks, clock rate, stack depth, memory
+    /// policy and seed.
+    pub fn prepare(&self, cpu: &mut Cpu) -> Result<(), MovieError> {
+        if cpu.rom_hash() != self.rom_hash {
+            return Err(MovieError::RomMismatch);
+        }
+        cpu.set_platform(self.platform);
+        cpu.set_quirks(self.quirks);
+        cpu.set_clock_hz(self.clock_hz);
+        cpu.set_stack_depth(self.stack_depth);
+        cpu.set_memory_policy(self.memory_policy);
+        cpu.seed_rng(self.seed);
+        Ok(())
+    }
+
+    /// Reads a movie written by [`save`](Movie::save).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Movie, MovieError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Writes the movie to `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+/// Plays a movie back on a machine prepared with [`Movie::prepare`], one
+/// frame at a time.
+#[derive(Clone, Debug)]
+pub struct Player {
+    movie: Movie,
+    frame: u64,
+    event: usize,
+    diverged: bool,
+}
+
+impl Player {
+    pub fn new(movie: Movie) -> Player {
+        Player {
+            movie,
+            frame: 0,
+            event: 0,
+            diverged: false,
+        }
+    }
+
+    /// Whether every recorded frame has been played.
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames
+    }
+
+    /// The frame about to be played.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Sets the keypad for the next frame. Call it before running the frame.
+    pub fn apply_input(&mut self, cpu: &mut Cpu) {
+        while let Some(e) = self.movie.events.get(self.event) {
+            if e.frame > self.frame {
+                break;
+            }
+            if e.pressed {
+                cpu.press_key(e.key);
+            } else {
+                cpu.release_key(e.key);
+            }
+            self.event += 1;
+        }
+    }
+
+    /// Checks the machine after the frame ran against the recording and
+    /// moves on to the next one. Returns the frame number the first time
+    /// the two differ.
+    pub fn check(&mut self, cpu: &Cpu) -> Option<u64> {
+        let frame = self.frame;
+        self.frame += 1;
+        match self.movie.checksums.get(&frame) {
+            Some(&sum) if !self.diverged && sum != checksum(cpu) => {
+                self.diverged = true;
+                Some(frame)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A 64-bit FNV-1a hash of the machine state, as saved by
+/// [`Cpu::to_state_bytes`].
+pub fn checksum(cpu: &Cpu) -> u64 {
+    cpu.to_state_bytes()
+        .iter()
+        .fold(0xCBF2_9CE4_8422_2325, |h, &b| {
+            (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01B3)
+        })
+}
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "chip8emu-movie {}", MOVIE_VERSION)?;
+        write!(f, "rom ")?;
+        for b in self.rom_hash.iter() {
+            write!(f, "{:02x}", b)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "platform {}", self.platform)?;
+        writeln!(f, "quirks {}", quirk_names(&self.quirks).join(" "))?;
+        writeln!(f, "hz {}", self.clock_hz)?;
+        writeln!(f, "stack {}", self.stack_depth)?;
+        writeln!(f, "memory {}", self.memory_policy)?;
+        writeln!(f, "frames {}", self.frames)?;
+
+        // Events and checksums in frame order, events first
+        let mut events = self.events.iter().peekable();
+        for (frame, sum) in self.checksums.iter() {
+            while let Some(e) = events.next_if(|e| e.frame <= *frame) {
+                write_event(f, e)?;
+            }
+            writeln!(f, "{} check {:016x}", frame, sum)?;
+        }
+        for e in events {
+            write_event(f, e)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_event(f: &mut fmt::Formatter, e: &KeyEvent) -> fmt::Result {
+    let verb = if e.pressed { "press" } else { "release" };
+    writeln!(f, "{} {} {:X}", e.frame, verb, e.key)
+}
+
+// Quirks as the words of the `quirks` line, `none` when all are off.
+fn quirk_names(q: &Quirks) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    let flags = [
+        (q.shift_vy, "shift_vy"),
+        (q.jump_vx, "jump_vx"),
+        (q.vf_reset, "vf_reset"),
+        (q.clip_sprites, "clip_sprites"),
+        (q.vip_random, "vip_random"),
+    ];
+    names.extend(flags.iter().filter(|f| f.0).map(|f| f.1));
+    match q.load_store {
+        LoadStore::Unchanged => {}
+        LoadStore::IncrementX => names.push("load_store_x"),
+        LoadStore::IncrementXPlusOne => names.push("load_store_x1"),
+    }
+    if names.is_empty() {
+        names.push("none");
+    }
+    names
+}
+
+impl FromStr for Movie {
+    type Err = MovieError;
+
+    fn from_str(s: &str) -> Result<Movie, MovieError> {
+        let mut lines = s.lines().enumerate();
+        let mut movie = Movie {
+            rom_hash: [0; 20],
+            seed: 0,
+            platform: Platform::Chip8,
+            quirks: Quirks::default(),
+            clock_hz: 0,
+            stack_depth: STACK_DEPTH,
+            memory_policy: MemoryPolicy::Wrap,
+            frames: 0,
+            events: Vec::new(),
+            checksums: BTreeMap::new(),
+            keys: [false; 16],
+        };
+
+        match lines.next() {
+            Some((_, l)) if l.trim() == format!("chip8emu-movie {}", MOVIE_VERSION) => {}
+            _ => return Err(parse_error(0, "not a chip8emu movie")),
+        }
+        for (n, line) in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let err = |message: &str| parse_error(n, message);
+            let value = |i: usize| words.get(i).copied().ok_or_else(|| err("missing a value"));
+            match words.first().copied() {
+                None => {}
+                Some("rom") => {
+                    let hex = value(1)?;
+                    if hex.len() != 40 || !hex.is_ascii() {
+                        return Err(err("the ROM hash should be 40 hex digits"));
+                    }
+                    for (i, b) in movie.rom_hash.iter_mut().enumerate() {
+                        *b = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                            .map_err(|_| err("the ROM hash should be 40 hex digits"))?;
+                    }
+                }
+                Some("seed") => movie.seed = value(1)?.parse().map_err(|_| err("bad seed"))?,
+                Some("platform") => {
+                    movie.platform = value(1)?.parse().map_err(|e: String| err(&e))?
+                }
+                Some("hz") => movie.clock_hz = value(1)?.parse().map_err(|_| err("bad rate"))?,
+                Some("stack") => {
+                    movie.stack_depth = value(1)?.parse().map_err(|_| err("bad stack depth"))?
+                }
+                Some("memory") => {
+                    movie.memory_policy = value(1)?.parse().map_err(|e: String| err(&e))?
+                }
+                Some("frames") => {
+                    movie.frames = value(1)?.parse().map_err(|_| err("bad frame count"))?
+                }
+                Some("quirks") => {
+                    let mut q = Quirks::default();
+                    for &name in words[1..].iter() {
+                        match name {
+                            "none" => {}
+                            "shift_vy" => q.shift_vy = true,
+                            "jump_vx" => q.jump_vx = true,
+                            "vf_reset" => q.vf_reset = true,
+                            "clip_sprites" => q.clip_sprites = true,
+                            "vip_random" => q.vip_random = true,
+                            "load_store_x" => q.load_store = LoadStore::IncrementX,
+                            "load_store_x1" => q.load_store = LoadStore::IncrementXPlusOne,
+                            _ => return Err(err(&format!("unknown quirk {:?}", name))),
+                        }
+                    }
+                    movie.quirks = q;
+                }
+                Some(first) => {
+                    let frame: u64 = first
+                        .parse()
+                        .map_err(|_| err(&format!("unknown line {:?}", first)))?;
+                    match value(1)? {
+                        "check" => {
+                            let sum = u64::from_str_radix(value(2)?, 16)
+                                .map_err(|_| err("bad checksum"))?;
+                            movie.checksums.insert(frame, sum);
+                        }
+                        verb @ ("press" | "release") => {
+                            let key = match u8::from_str_radix(value(2)?, 16) {
+                                Ok(k) if k < 16 => k,
+                                _ => return Err(err("keys go from 0 to F")),
+                            };
+                            movie.events.push(KeyEvent {
+                                frame,
+                                key,
+                                pressed: verb == "press",
+                            });
+                        }
+                        w => return Err(err(&format!("unknown line {:?}", w))),
+                    }
+                }
+            }
+        }
+        if movie.clock_hz == 0 {
+            return Err(parse_error(0, "missing the hz line"));
+        }
+        movie.events.sort_by_key(|e| e.frame);
+        Ok(movie)
+    }
+}
+
+fn parse_error(n: usize, message: &str) -> MovieError {
+    MovieError::Parse {
+        line: n + 1,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+#[path = "./movie_tests.rs"]
+mod movie_tests;
@@ -0,0 +1,109 @@
+use super::*;
+use crate::headless::{replay, Outcome};
+
+// F00A C1FF 1200: waits for a key, draws a random number, starts over
+const ROM: [u8; 6] = [0xF0, 0x0A, 0xC1, 0xFF, 0x12, 0x00];
+
+fn record() -> Movie {
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    cpu.seed_rng(99);
+    let mut movie = Movie::new(&cpu, 99);
+    for frame in 0..30 {
+        if frame % 10 == 2 {
+            cpu.press_key(0xB);
+        } else if frame % 10 == 4 {
+            cpu.release_key(0xB);
+        }
+        cpu.run_frame().unwrap();
+        movie.record_frame(&cpu);
+    }
+    movie
+}
+
+#[test]
+fn test_record() {
+    let movie = record();
+    assert_eq!(movie.frames, 30);
+    assert_eq!(movie.events.len(), 6);
+    assert_eq!(
+        movie.events[1],
+        KeyEvent {
+            frame: 4,
+            key: 0xB,
+            pressed: false
+        }
+    );
+    assert_eq!(movie.checksums.len(), 30);
+}
+
+#[test]
+fn test_text_round_trip() {
+    let movie = record();
+    let text = movie.to_string();
+    assert!(text.starts_with("chip8emu-movie 1\nrom "));
+    assert!(text.contains("\nquirks none\nhz 700\nstack 16\nmemory wrap\nframes 30\n"));
+    assert!(text.contains("\n2 press B\n2 check "));
+    let parsed: Movie = text.parse().unwrap();
+    assert_eq!(parsed.rom_hash, movie.rom_hash);
+    assert_eq!(parsed.seed, 99);
+    assert_eq!(parsed.events, movie.events);
+    assert_eq!(parsed.checksums, movie.checksums);
+}
+
+#[test]
+fn test_replay() {
+    let movie = record();
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    assert_eq!(
+        replay(&mut cpu, &movie, None).unwrap(),
+        (Outcome::Completed, 30)
+    );
+
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    let mut player = Player::new(movie.clone());
+    movie.prepare(&mut cpu).unwrap();
+    while !player.is_finished() {
+        player.apply_input(&mut cpu);
+        cpu.run_frame().unwrap();
+        assert_eq!(player.check(&cpu), None);
+    }
+}
+
+#[test]
+fn test_divergence() {
+    let mut movie = record();
+    *movie.checksums.get_mut(&7).unwrap() ^= 1;
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    assert_eq!(
+        replay(&mut cpu, &movie, None).unwrap(),
+        (Outcome::Diverged(7), 8)
+    );
+
+    // The random generator is part of the checksum
+    movie.seed = 100;
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    assert_eq!(
+        replay(&mut cpu, &movie, None).unwrap(),
+        (Outcome::Diverged(0), 1)
+    );
+
+    let mut cpu = Cpu::initialize(&[0x12, 0x00], Quirks::default());
+    match replay(&mut cpu, &movie, None) {
+        Err(MovieError::RomMismatch) => {}
+        r => panic!("expected a ROM mismatch, got {:?}", r),
+    }
+}
+
+#[test]
+fn test_parse_errors() {
+    let header = "chip8emu-movie 1\nhz 700\n";
+    match format!("{}3 hold 1\n", header).parse::<Movie>() {
+        Err(MovieError::Parse { line, .. }) => assert_eq!(line, 3),
+        r => panic!("expected a parse error, got {:?}", r),
+    }
+    assert!("chip8emu-movie 9\nhz 700\n".parse::<Movie>().is_err());
+    assert!("chip8emu-movie 1\nquirks shift_vy wobbly\nhz 1\n"
+        .parse::<Movie>()
+        .is_err());
+    assert!(format!("{}rom 12ab\n", header).parse::<Movie>().is_err());
+}
@@ -0,0 +1,135 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// A red, green, blue colour.
+pub type Rgb = (u8, u8, u8);
+
+/// Colour of each pixel value: off, plane 1, plane 2 and both XO-CHIP planes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Palette(pub [Rgb; 4]);
+
+impl Palette {
+    /// Green on black, the emulator's original look.
+    pub const CLASSIC: Palette = Palette([(0, 0, 0), (0, 250, 0), (0, 110, 0), (170, 250, 170)]);
+
+    /// The built-in themes by name, [`CLASSIC`](Palette::CLASSIC) first.
+    pub const THEMES: [(&'static str, Palette); 5] = [
+        ("classic", Palette::CLASSIC),
+        // An amber phosphor monitor
+        (
+            "amber",
+            Palette([(18, 10, 0), (255, 176, 0), (160, 104, 0), (255, 217, 138)]),
+        ),
+        // Dark pixels on a grey-green liquid crystal screen
+        (
+            "lcd",
+            Palette([(180, 184, 168), (40, 44, 36), (122, 126, 112), (76, 80, 70)]),
+        ),
+        (
+            "high-contrast",
+            Palette([(0, 0, 0), (255, 255, 255), (255, 255, 0), (0, 255, 255)]),
+        ),
+        // Okabe-Ito colours, told apart with any colour vision
+        (
+            "colorblind",
+            Palette([(0, 0, 0), (230, 159, 0), (86, 180, 233), (255, 255, 255)]),
+        ),
+    ];
+
+    /// The theme called `name`.
+    pub fn theme(name: &str) -> Option<Palette> {
+        Palette::THEMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|&(_, p)| p)
+    }
+
+    /// The name of the theme this palette is, if it is one.
+    pub fn theme_name(&self) -> Option<&'static str> {
+        Palette::THEMES
+            .iter()
+            .find(|(_, p)| p == self)
+            .map(|&(n, _)| n)
+    }
+
+    /// The theme after this one, going back to the first after the last or
+    /// for a palette that isn't a theme.
+    pub fn next_theme(&self) -> Palette {
+        let i = Palette::THEMES.iter().position(|(_, p)| p == self);
+        let next = i.map_or(0, |i| (i + 1) % Palette::THEMES.len());
+        Palette::THEMES[next].1
+    }
+
+    /// The colour of a `gfx` pixel value.
+    pub fn color(&self, pixel: u8) -> Rgb {
+        self.0[(pixel & 0x3) as usize]
+    }
+
+    /// Builds a palette from two (background, foreground) or four
+    /// `#rrggbb` colours. With two, both XO-CHIP planes use the foreground.
+    pub fn from_hex(colors: &[&str]) -> Result<Palette, String> {
+        let rgb = colors
+            .iter()
+            .map(|c| parse_rgb(c))
+            .collect::<Result<Vec<Rgb>, String>>()?;
+        match rgb[..] {
+            [bg, fg] => Ok(Palette([bg, fg, fg, fg])),
+            [a, b, c, d] => Ok(Palette([a, b, c, d])),
+            _ => Err(format!("a palette has 2 or 4 colours, not {}", rgb.len())),
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::CLASSIC
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Parses a theme name or comma separated colours, e.g.
+    /// `#000000,#ffb000`.
+    fn from_str(s: &str) -> Result<Palette, String> {
+        if let Some(p) = Palette::theme(s) {
+            return Ok(p);
+        }
+        if !s.contains(',') {
+            let names: Vec<&str> = Palette::THEMES.iter().map(|&(n, _)| n).collect();
+            return Err(format!("unknown theme {:?}, try {}", s, names.join(", ")));
+        }
+        let colors: Vec<&str> = s.split(',').map(str::trim).collect();
+        Palette::from_hex(&colors)
+    }
+}
+
+impl TryFrom<String> for Palette {
+    type Error = String;
+
+    /// As [`from_str`](Palette::from_str), so configuration files can hold
+    /// palettes.
+    fn try_from(s: String) -> Result<Palette, String> {
+        s.parse()
+    }
+}
+
+// `#rrggbb`, the # being optional.
+fn parse_rgb(s: &str) -> Result<Rgb, String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|h| u8::from_str_radix(h, 16).ok())
+    };
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok((r, g, b)),
+        _ => Err(format!("{:?} isn't a #rrggbb colour", s)),
+    }
+}
+
+#[cfg(test)]
+#[path = "./palette_tests.rs"]
+mod palette_tests;
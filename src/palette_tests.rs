@@ -0,0 +1,48 @@
+use super::*;
+
+#[test]
+fn test_parse() {
+    let p: Palette = "#000000, #FFB000".parse().unwrap();
+    assert_eq!(p.color(0), (0, 0, 0));
+    assert_eq!(p.color(1), (0xFF, 0xB0, 0));
+    assert_eq!(p.color(3), (0xFF, 0xB0, 0));
+
+    let p: Palette = "101010,202020,303030,404040".parse().unwrap();
+    assert_eq!(p.color(2), (0x30, 0x30, 0x30));
+
+    assert!("#000000".parse::<Palette>().is_err());
+    assert!("#000000,".parse::<Palette>().is_err());
+    assert!("#000000,#12345".parse::<Palette>().is_err());
+    assert!("#000000,#12345g".parse::<Palette>().is_err());
+    assert_eq!(Palette::default(), Palette::CLASSIC);
+}
+
+#[test]
+fn test_themes() {
+    assert_eq!("classic".parse::<Palette>(), Ok(Palette::CLASSIC));
+    let amber: Palette = "amber".parse().unwrap();
+    assert_eq!(amber.color(1), (255, 176, 0));
+    assert_eq!(amber.theme_name(), Some("amber"));
+    assert!("sepia".parse::<Palette>().unwrap_err().contains("amber"));
+    assert_eq!(Palette::theme("lcd").unwrap().theme_name(), Some("lcd"));
+
+    // Every theme tells its four pixel values apart
+    for (name, p) in Palette::THEMES.iter() {
+        for a in 0..4 {
+            for b in a + 1..4 {
+                assert_ne!(p.color(a), p.color(b), "{} {} {}", name, a, b);
+            }
+        }
+    }
+
+    // Cycling visits each theme once, starting over from custom palettes
+    let mut p = Palette::CLASSIC;
+    for &(name, _) in Palette::THEMES[1..].iter() {
+        p = p.next_theme();
+        assert_eq!(p.theme_name(), Some(name));
+    }
+    assert_eq!(p.next_theme(), Palette::CLASSIC);
+    let custom: Palette = "#000000,#010101".parse().unwrap();
+    assert_eq!(custom.theme_name(), None);
+    assert_eq!(custom.next_theme(), Palette::CLASSIC);
+}
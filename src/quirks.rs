@@ -0,0 +1,120 @@
+use std::str::FromStr;
+
+use crate::cpu::Platform;
+
+/// What `FX55` and `FX65` do to `I` after copying the registers.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LoadStore {
+    /// `I` is left alone (SUPER-CHIP 1.1).
+    #[default]
+    Unchanged,
+    /// `I` is increased by X (CHIP-48).
+    IncrementX,
+    /// `I` is left pointing after the last register, increased by X + 1
+    /// (COSMAC VIP, XO-CHIP).
+    IncrementXPlusOne,
+}
+
+/// Opcodes whose behaviour differs between CHIP-8 interpreters.
+///
+/// ROMs are usually written against one interpreter and misbehave on the
+/// others, so pick the preset matching the platform a ROM was made for.
+/// The default keeps this emulator's historical behaviour: shifts in place,
+/// `I` untouched by `FX55`/`FX65`, `BNNN` using `V0`, no `VF` reset and
+/// wrapping sprites.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` shift VY and store the result in VX, instead of shifting
+    /// VX in place.
+    pub shift_vy: bool,
+    /// How `FX55`/`FX65` move `I`.
+    pub load_store: LoadStore,
+    /// `BNNN` behaves as `BXNN`, jumping to XNN plus VX instead of NNN plus V0.
+    pub jump_vx: bool,
+    /// `8XY1`, `8XY2` and `8XY3` reset VF to 0.
+    pub vf_reset: bool,
+    /// Sprites are cut off at the screen edges instead of wrapping around.
+    /// The starting position still wraps.
+    pub clip_sprites: bool,
+    /// `CXNN` draws from a reconstruction of the COSMAC VIP random routine,
+    /// not checked against a real VIP, instead of the xorshift generator. Off in every preset, it has to be asked for.
+    pub vip_random: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub fn cosmac_vip() -> Quirks {
+        Quirks {
+            shift_vy: true,
+            load_store: LoadStore::IncrementXPlusOne,
+            jump_vx: false,
+            vf_reset: true,
+            clip_sprites: true,
+            vip_random: false,
+        }
+    }
+
+    /// CHIP-48 on the HP-48 calculators.
+    pub fn chip48() -> Quirks {
+        Quirks {
+            shift_vy: false,
+            load_store: LoadStore::IncrementX,
+            jump_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            vip_random: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1.
+    pub fn schip() -> Quirks {
+        Quirks {
+            shift_vy: false,
+            load_store: LoadStore::Unchanged,
+            jump_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            vip_random: false,
+        }
+    }
+
+    /// XO-CHIP, as implemented by Octo.
+    pub fn xochip() -> Quirks {
+        Quirks {
+            shift_vy: true,
+            load_store: LoadStore::IncrementXPlusOne,
+            jump_vx: false,
+            vf_reset: false,
+            clip_sprites: false,
+            vip_random: false,
+        }
+    }
+}
+
+impl Quirks {
+    /// The usual preset for ROMs written for `platform`: plain CHIP-8 ROMs
+    /// get the COSMAC VIP's behaviour.
+    pub fn for_platform(platform: Platform) -> Quirks {
+        match platform {
+            Platform::Chip8 => Quirks::cosmac_vip(),
+            Platform::SuperChip => Quirks::schip(),
+            Platform::XoChip => Quirks::xochip(),
+        }
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = String;
+
+    /// Parses a preset name: `default`, `vip`, `chip48`, `schip` or `xochip`.
+    fn from_str(s: &str) -> Result<Quirks, String> {
+        match s {
+            "default" => Ok(Quirks::default()),
+            "vip" => Ok(Quirks::cosmac_vip()),
+            "chip48" => Ok(Quirks::chip48()),
+            "schip" => Ok(Quirks::schip()),
+            "xochip" => Ok(Quirks::xochip()),
+            _ => Err(format!("unknown quirks preset {:?}", s)),
+        }
+    }
+}
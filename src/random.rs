@@ -0,0 +1,93 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where `CXNN` gets its random bytes from: a seeded xorshift generator, or
+/// a routine in the style of the COSMAC VIP interpreter's when the
+/// [`vip_random`](crate::quirks::Quirks::vip_random) quirk is set.
+///
+/// It is part of the machine, so a save state carries it and two machines
+/// seeded alike draw the same numbers.
+///
+/// ```
+/// use chip8emu::random::Random;
+///
+/// let (mut a, mut b) = (Random::new(42), Random::new(42));
+/// assert_eq!(a.next_byte(), b.next_byte());
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Random {
+    pub(crate) state: u64, // xorshift64* state, never 0
+    pub(crate) r9: u16,    // The VIP's R9 register, its random seed
+}
+
+impl Random {
+    /// A generator that always draws the same numbers for the same `seed`.
+    pub fn new(seed: u64) -> Random {
+        // One splitmix64 round so nearby seeds start far apart
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Random {
+            state: if z == 0 { 1 } else { z },
+            r9: (z >> 48) as u16,
+        }
+    }
+
+    /// The next byte from the xorshift generator.
+    pub fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    /// The next byte from a reconstruction of the VIP interpreter's `CXNN`:
+    /// step R9, add the byte at R9.0 in [`VIP_PAGE`] to R9.1, then add that
+    /// sum rotated right through the carry to it. The result becomes R9.1.
+    ///
+    /// Neither these steps nor the table have been checked against a real
+    /// VIP, so the numbers aren't guaranteed to be the ones it drew.
+    pub fn next_vip_byte(&mut self) -> u8 {
+        self.r9 = self.r9.wrapping_add(1);
+        let [hi, lo] = self.r9.to_be_bytes();
+        let (sum, carry) = hi.overflowing_add(VIP_PAGE[lo as usize]);
+        let hi = sum.wrapping_add(sum >> 1 | (carry as u8) << 7);
+        self.r9 = u16::from_be_bytes([hi, lo]);
+        hi
+    }
+}
+
+/// The table [`next_vip_byte`](Random::next_vip_byte) reads, standing in for
+/// the 0x100 - 0x1FF page of the COSMAC VIP's CHIP-8 interpreter. It was
+/// pieced together from the interpreter's instruction handlers rather than
+/// copied from a dump, and the few bytes between them are guessed as 0.
+pub const VIP_PAGE: [u8; 256] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x45, 0xA3, 0x98, 0x56, 0xD4, 0xF8, 0x81, 0xBC, 0xF8, 0x95, 0xAC,
+    0x22, 0xDC, 0x12, 0x56, 0xD4, 0x06, 0xB8, 0xD4, 0x06, 0xA8, 0xD4, 0x64, 0x0A, 0x01, 0xE6, 0x8A,
+    0xF4, 0xAA, 0x3B, 0x28, 0x9A, 0xFC, 0x01, 0xBA, 0xD4, 0xF8, 0x81, 0xBA, 0x06, 0xFA, 0x0F, 0xAA,
+    0x0A, 0xAA, 0xD4, 0xE6, 0x06, 0xBF, 0x93, 0xBE, 0xF8, 0x1B, 0xAE, 0x2A, 0x1A, 0xF8, 0x00, 0x5A,
+    0x0E, 0xF5, 0x3B, 0x4B, 0x56, 0x0A, 0xFC, 0x01, 0x5A, 0x30, 0x40, 0x4E, 0xF6, 0x3B, 0x3C, 0x9F,
+    0x56, 0x2A, 0x2A, 0xD4, 0x00, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x07, 0x5A, 0x87, 0xF3, 0x17,
+    0x1A, 0x3A, 0x5B, 0x12, 0xD4, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x0A, 0x57, 0x87, 0xF3, 0x17,
+    0x1A, 0x3A, 0x6B, 0x12, 0xD4, 0x15, 0x85, 0x22, 0x73, 0x95, 0x52, 0x25, 0x45, 0xA5, 0x86, 0xFA,
+    0x0F, 0xB5, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x82, 0x15, 0x15, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x88,
+    0xD4, 0x45, 0x07, 0x30, 0x8C, 0x45, 0x07, 0x30, 0x84, 0xE6, 0x62, 0x26, 0x45, 0xA3, 0x36, 0x88,
+    0xD4, 0x3E, 0x88, 0xD4, 0xF8, 0xF0, 0xA7, 0xE7, 0x45, 0xF4, 0xA5, 0x86, 0xFA, 0x0F, 0x3B, 0xB2,
+    0xFC, 0x01, 0xB5, 0xD4, 0x45, 0x56, 0xD4, 0x45, 0xE6, 0xF4, 0x56, 0xD4, 0x45, 0xFA, 0x0F, 0x3A,
+    0xC4, 0x07, 0x56, 0xD4, 0xAF, 0x22, 0xF8, 0xD3, 0x73, 0x8F, 0xF9, 0xF0, 0x52, 0xE6, 0x07, 0xD2,
+    0x56, 0xF8, 0xFF, 0xA6, 0xF8, 0x00, 0x7E, 0x56, 0xD4, 0x19, 0x89, 0xAE, 0x93, 0xBE, 0x99, 0xEE,
+    0xF4, 0x56, 0x76, 0xE6, 0xF4, 0xB9, 0x56, 0x45, 0xF2, 0x56, 0xD4, 0x45, 0xAA, 0x86, 0xFA, 0x0F,
+    0xBA, 0xD4, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE0, 0x00, 0x4B,
+];
+
+/// A seed that differs from run to run, taken from the clock.
+pub fn entropy_seed() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    now.as_secs() ^ ((now.subsec_nanos() as u64) << 32) ^ std::process::id() as u64
+}
+
+#[cfg(test)]
+#[path = "./random_tests.rs"]
+mod random_tests;
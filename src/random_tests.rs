@@ -0,0 +1,30 @@
+use super::*;
+
+#[test]
+fn test_seeded() {
+    let mut a = Random::new(7);
+    let mut b = Random::new(7);
+    let drawn: Vec<u8> = (0..64).map(|_| a.next_byte()).collect();
+    assert!(drawn.iter().all(|&r| r == b.next_byte()));
+    // Not stuck on one value
+    assert!(drawn.iter().any(|&r| r != drawn[0]));
+
+    let mut c = Random::new(8);
+    assert!(drawn.iter().any(|&r| r != c.next_byte()));
+}
+
+#[test]
+fn test_vip() {
+    // Table bytes 0xD9 - 0xDB: 19 89 AE
+    let mut r = Random {
+        state: 1,
+        r9: 0x00D8,
+    };
+    let drawn: Vec<u8> = (0..3).map(|_| r.next_vip_byte()).collect();
+    assert_eq!(drawn, vec![0x25, 0x05, 0x0C]);
+    assert_eq!(r.r9, 0x0CDB);
+
+    // A carry out of the add is rotated back in: 0xF0 + 0x19
+    r.r9 = 0xF0D8;
+    assert_eq!(r.next_vip_byte(), 0x09 + 0x84);
+}
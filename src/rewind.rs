@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+
+use crate::cpu::Cpu;
+
+/// Snapshots kept by default, 10 seconds at one per frame.
+pub const DEFAULT_REWIND_LEN: usize = 600;
+
+/// Frames between snapshots by default.
+pub const DEFAULT_REWIND_INTERVAL: u32 = 1;
+
+// How to get from one snapshot back to the one before it.
+enum Entry {
+    // XOR against the newer snapshot, zero runs squeezed out
+    Delta(Vec<u8>),
+    // The state size changed in between (the platform did), so keep it whole
+    Full(Vec<u8>),
+}
+
+/// A ring buffer of machine snapshots to step back through.
+///
+/// Call [`record`](Rewind::record) after every frame and
+/// [`step_back`](Rewind::step_back) for every frame played backwards. Only
+/// the newest snapshot is kept whole, older ones are stored as a compressed
+/// delta against their successor, so a few seconds cost little memory.
+pub struct Rewind {
+    capacity: usize,
+    interval: u32,
+    frames: u32,
+    newest: Option<Vec<u8>>,
+    older: VecDeque<Entry>,
+}
+
+impl Rewind {
+    /// Keeps up to `capacity` snapshots, taking one every `interval` frames.
+    /// A `capacity` of 0 records nothing.
+    pub fn new(capacity: usize, interval: u32) -> Rewind {
+        Rewind {
+            capacity,
+            interval: interval.max(1),
+            frames: 0,
+            newest: None,
+            older: VecDeque::new(),
+        }
+    }
+
+    /// Number of snapshots that can still be stepped back to.
+    pub fn len(&self) -> usize {
+        self.newest.as_ref().map_or(0, |_| self.older.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// Drops every snapshot, e.g. after loading another ROM.
+    pub fn clear(&mut self) {
+        self.frames = 0;
+        self.newest = None;
+        self.older.clear();
+    }
+
+    /// Counts a frame of `cpu`, snapshotting it every `interval` frames.
+    pub fn record(&mut self, cpu: &Cpu) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.frames += 1;
+        if self.frames < self.interval {
+            return;
+        }
+        self.frames = 0;
+
+        let state = cpu.to_state_bytes();
+        if let Some(prev) = self.newest.take() {
+            let entry = if prev.len() == state.len() {
+                let diff: Vec<u8> = prev.iter().zip(state.iter()).map(|(a, b)| a ^ b).collect();
+                Entry::Delta(compress(&diff))
+            } else {
+                Entry::Full(prev)
+            };
+            self.older.push_back(entry);
+            if self.older.len() >= self.capacity {
+                self.older.pop_front();
+            }
+        }
+        self.newest = Some(state);
+    }
+
+    /// Puts `cpu` back to the newest snapshot older than its current state
+    /// and forgets it, so the next call goes further back. Returns false once
+    /// there's nothing left.
+    pub fn step_back(&mut self, cpu: &mut Cpu) -> bool {
+        let mut state = match self.pop_newest() {
+            Some(s) => s,
+            None => return false,
+        };
+        // The snapshot recorded after the last frame is where cpu already is
+        if state == cpu.to_state_bytes() {
+            state = match self.pop_newest() {
+                Some(s) => s,
+                None => return false,
+            };
+        }
+        if cpu.from_state_bytes(&state).is_err() {
+            // Snapshots of another ROM are no use
+            self.clear();
+            return false;
+        }
+        self.frames = 0;
+        true
+    }
+
+    // Takes the newest snapshot, rebuilding the one before it from its delta.
+    fn pop_newest(&mut self) -> Option<Vec<u8>> {
+        let state = self.newest.take()?;
+        self.newest = self.older.pop_back().map(|entry| match entry {
+            Entry::Delta(d) => {
+                let diff = decompress(&d, state.len());
+                state.iter().zip(diff.iter()).map(|(a, b)| a ^ b).collect()
+            }
+            Entry::Full(s) => s,
+        });
+        Some(state)
+    }
+}
+
+impl Default for Rewind {
+    fn default() -> Rewind {
+        Rewind::new(DEFAULT_REWIND_LEN, DEFAULT_REWIND_INTERVAL)
+    }
+}
+
+// Run-length encodes the zeros of an XOR delta: a 0 is followed by how many
+// zeros (1 - 255) it stands for, any other byte stands for itself.
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] != 0 {
+            out.push(data[i]);
+            i += 1;
+            continue;
+        }
+        let mut run = 0;
+        while i < data.len() && data[i] == 0 && run < 255 {
+            run += 1;
+            i += 1;
+        }
+        out.push(0);
+        out.push(run as u8);
+    }
+    out
+}
+
+fn decompress(data: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    let mut it = data.iter();
+    while let Some(&b) = it.next() {
+        if b == 0 {
+            let run = *it.next().unwrap_or(&0) as usize;
+            out.resize(out.len() + run, 0);
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+#[path = "./rewind_tests.rs"]
+mod rewind_tests;
@@ -0,0 +1,103 @@
+use super::*;
+use crate::cpu::Platform;
+use crate::quirks::Quirks;
+
+// 7001 1200: counts up in V0 forever
+const ROM: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+fn counter() -> Cpu {
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    // One add and one jump per frame
+    cpu.set_clock_hz(2 * crate::cpu::TIMER_HZ);
+    cpu
+}
+
+#[test]
+fn test_compress() {
+    let data = [0, 0, 0, 7, 0, 9, 9];
+    assert_eq!(compress(&data), vec![0, 3, 7, 0, 1, 9, 9]);
+    assert_eq!(decompress(&compress(&data), data.len()), data);
+
+    let zeros = [0; 600];
+    assert_eq!(compress(&zeros), vec![0, 255, 0, 255, 0, 90]);
+    assert_eq!(decompress(&compress(&zeros), zeros.len()), zeros.to_vec());
+}
+
+#[test]
+fn test_step_back() {
+    let mut cpu = counter();
+    let mut rewind = Rewind::new(100, 1);
+    for _ in 0..10 {
+        cpu.run_frame().unwrap();
+        rewind.record(&cpu);
+    }
+    assert_eq!(cpu.v[0], 10);
+    assert_eq!(rewind.len(), 10);
+
+    for expected in (1..10).rev() {
+        assert!(rewind.step_back(&mut cpu));
+        assert_eq!(cpu.v[0], expected);
+    }
+    assert!(!rewind.step_back(&mut cpu));
+    assert_eq!(cpu.v[0], 1);
+    assert!(rewind.is_empty());
+}
+
+#[test]
+fn test_first_step_back() {
+    let mut cpu = counter();
+    let mut rewind = Rewind::new(100, 1);
+    for _ in 0..3 {
+        cpu.run_frame().unwrap();
+        rewind.record(&cpu);
+    }
+    // The newest snapshot is the frame on screen, so one step is one frame
+    assert!(rewind.step_back(&mut cpu));
+    assert_eq!(cpu.v[0], 2);
+
+    // Unless a frame has run since it was taken
+    cpu.run_frame().unwrap();
+    assert!(rewind.step_back(&mut cpu));
+    assert_eq!(cpu.v[0], 1);
+}
+
+#[test]
+fn test_capacity_and_interval() {
+    let mut cpu = counter();
+    let mut rewind = Rewind::new(3, 2);
+    for _ in 0..10 {
+        cpu.run_frame().unwrap();
+        rewind.record(&cpu);
+    }
+    // Snapshots at 2, 4, 6, 8 and 10, only the last three kept
+    assert_eq!(rewind.len(), 3);
+    let mut seen = Vec::new();
+    while rewind.step_back(&mut cpu) {
+        seen.push(cpu.v[0]);
+    }
+    assert_eq!(seen, vec![8, 6]);
+}
+
+#[test]
+fn test_platform_change() {
+    let mut cpu = counter();
+    let mut rewind = Rewind::new(10, 1);
+    cpu.run_frame().unwrap();
+    rewind.record(&cpu);
+    cpu.set_platform(Platform::XoChip);
+    cpu.run_frame().unwrap();
+    rewind.record(&cpu);
+
+    assert!(rewind.step_back(&mut cpu));
+    assert_eq!(cpu.platform(), Platform::Chip8);
+    assert_eq!(cpu.v[0], 1);
+}
+
+#[test]
+fn test_disabled() {
+    let mut cpu = counter();
+    let mut rewind = Rewind::new(0, 1);
+    cpu.run_frame().unwrap();
+    rewind.record(&cpu);
+    assert!(!rewind.step_back(&mut cpu));
+}
@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::cpu::Platform;
+use crate::palette::Palette;
+use crate::quirks::{LoadStore, Quirks};
+
+// The database shipped with the emulator.
+const BUNDLED: &str = include_str!("../data/roms.json");
+
+/// Why a ROM database couldn't be read.
+#[derive(Debug)]
+pub enum DbError {
+    Io(io::Error),
+    /// The file isn't JSON in the database's shape.
+    Json(serde_json::Error),
+    /// An entry has a value out of range, e.g. an unknown platform.
+    Invalid(String),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbError::Io(e) => write!(f, "{}", e),
+            DbError::Json(e) => write!(f, "{}", e),
+            DbError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl error::Error for DbError {}
+
+impl From<io::Error> for DbError {
+    fn from(e: io::Error) -> DbError {
+        DbError::Io(e)
+    }
+}
+
+// Instructions per frame beyond any real game, and far from overflowing
+// once turned into a clock rate
+const MAX_TICKRATE: u32 = 100_000;
+
+/// What the database knows about a ROM. Anything left out is up to the
+/// user or the emulator's defaults.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    /// Instructions per 60 Hz frame.
+    pub tickrate: Option<u32>,
+    pub palette: Option<Palette>,
+    /// What the game uses keypad keys for, e.g. `up` -> 0x5.
+    pub keys: BTreeMap<String, u8>,
+}
+
+impl RomInfo {
+    /// The title followed by the authors, if known.
+    pub fn credits(&self) -> String {
+        if self.authors.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{} by {}", self.title, self.authors.join(", "))
+        }
+    }
+}
+
+/// Program information keyed by the SHA-1 of the ROM.
+///
+/// The JSON groups ROMs by program, since a game often has several
+/// releases:
+///
+/// ```json
+/// { "programs": [ {
+///     "title": "Pong", "authors": ["Paul Vervalin"],
+///     "roms": { "<sha1>": {
+///         "platform": "chip8", "quirks": "vip", "tickrate": 15,
+///         "palette": ["#000000", "#ffffff"], "keys": { "up": 1, "down": 4 }
+///     } }
+/// } ] }
+/// ```
+///
+/// `quirks` is a preset name, as for `--quirks`, or an object with the
+/// [`Quirks`] field names, `load_store` being `unchanged`, `x` or `x+1`.
+#[derive(Clone, Debug, Default)]
+pub struct Database {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl Database {
+    /// The database shipped with the emulator.
+    pub fn bundled() -> Database {
+        Database::from_json(BUNDLED).expect("the bundled ROM database is valid")
+    }
+
+    /// Reads a database file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Database, DbError> {
+        Database::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Parses a database from JSON.
+    pub fn from_json(text: &str) -> Result<Database, DbError> {
+        let file: File = serde_json::from_str(text).map_err(DbError::Json)?;
+        let mut roms = HashMap::new();
+        for program in file.programs {
+            for (sha1, entry) in program.roms {
+                let invalid = |msg: String| DbError::Invalid(format!("{}: {}", sha1, msg));
+                if sha1.len() != 40 || !sha1.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(invalid("isn't a SHA-1".to_string()));
+                }
+                let info = entry
+                    .into_info(&program.title, &program.authors)
+                    .map_err(invalid)?;
+                roms.insert(sha1.to_ascii_lowercase(), info);
+            }
+        }
+        Ok(Database { roms })
+    }
+
+    /// Adds the entries of `other`, replacing ours for the same ROMs.
+    pub fn merge(&mut self, other: Database) {
+        self.roms.extend(other.roms);
+    }
+
+    /// The entry for the ROM with this SHA-1, in hex.
+    pub fn lookup(&self, sha1: &str) -> Option<&RomInfo> {
+        self.roms.get(&sha1.to_ascii_lowercase())
+    }
+
+    /// Number of ROMs known.
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+// The JSON layout, turned into `RomInfo`s once read.
+#[derive(Deserialize)]
+struct File {
+    programs: Vec<Program>,
+}
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    roms: BTreeMap<String, RomEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RomEntry {
+    platform: Option<String>,
+    quirks: Option<QuirksEntry>,
+    tickrate: Option<u32>,
+    palette: Option<Vec<String>>,
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QuirksEntry {
+    Preset(String),
+    Flags(QuirkFlags),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct QuirkFlags {
+    shift_vy: bool,
+    load_store: Option<String>,
+    jump_vx: bool,
+    vf_reset: bool,
+    clip_sprites: bool,
+    vip_random: bool,
+}
+
+impl RomEntry {
+    fn into_info(self, title: &str, authors: &[String]) -> Result<RomInfo, String> {
+        let quirks = match self.quirks {
+            None => None,
+            Some(QuirksEntry::Preset(name)) => Some(name.parse()?),
+            Some(QuirksEntry::Flags(f)) => Some(Quirks {
+                shift_vy: f.shift_vy,
+                load_store: match f.load_store.as_deref() {
+                    None | Some("unchanged") => LoadStore::Unchanged,
+                    Some("x") => LoadStore::IncrementX,
+                    Some("x+1") => LoadStore::IncrementXPlusOne,
+                    Some(other) => return Err(format!("unknown load_store {:?}", other)),
+                },
+                jump_vx: f.jump_vx,
+                vf_reset: f.vf_reset,
+                clip_sprites: f.clip_sprites,
+                vip_random: f.vip_random,
+            }),
+        };
+        if let Some(t) = self.tickrate.filter(|t| !(1..=MAX_TICKRATE).contains(t)) {
+            return Err(format!(
+                "tickrate {} isn't between 1 and {}",
+                t, MAX_TICKRATE
+            ));
+        }
+        if let Some((name, _)) = self.keys.iter().find(|(_, &k)| k > 0xF) {
+            return Err(format!("key {:?} isn't on the keypad", name));
+        }
+        Ok(RomInfo {
+            title: title.to_string(),
+            authors: authors.to_vec(),
+            platform: self.platform.map(|p| p.parse()).transpose()?,
+            quirks,
+            tickrate: self.tickrate,
+            palette: match self.palette {
+                Some(colors) => {
+                    let colors: Vec<&str> = colors.iter().map(String::as_str).collect();
+                    Some(Palette::from_hex(&colors)?)
+                }
+                None => None,
+            },
+            keys: self.keys,
+        })
+    }
+}
+
+#[cfg(test)]
+#[path = "./romdb_tests.rs"]
+mod romdb_tests;
@@ -0,0 +1,96 @@
+use super::*;
+
+const PONG: &str = "b232ef880bd6060fb45fa6effed7edf0ae95670e";
+
+#[test]
+fn test_bundled() {
+    let db = Database::bundled();
+    assert!(!db.is_empty());
+    let pong = db.lookup(&PONG.to_ascii_uppercase()).unwrap();
+    assert_eq!(pong.credits(), "Pong by Paul Vervalin");
+    assert_eq!(pong.platform, Some(Platform::Chip8));
+    assert_eq!(pong.keys["left up"], 0x1);
+    assert!(db
+        .lookup("0000000000000000000000000000000000000000")
+        .is_none());
+}
+
+#[test]
+fn test_entry_fields() {
+    let db = Database::from_json(
+        r##"{ "programs": [ { "title": "Game", "roms": {
+            "1111111111111111111111111111111111111111": {
+                "platform": "schip", "quirks": "vip", "tickrate": 30,
+                "palette": ["#000000", "#ffffff"]
+            },
+            "2222222222222222222222222222222222222222": {
+                "quirks": { "shift_vy": true, "load_store": "x+1" }
+            }
+        } } ] }"##,
+    )
+    .unwrap();
+    assert_eq!(db.len(), 2);
+    let a = db
+        .lookup("1111111111111111111111111111111111111111")
+        .unwrap();
+    assert_eq!(a.credits(), "Game");
+    assert_eq!(a.platform, Some(Platform::SuperChip));
+    assert_eq!(a.quirks, Some(Quirks::cosmac_vip()));
+    assert_eq!(a.tickrate, Some(30));
+    assert_eq!(a.palette.unwrap().color(1), (255, 255, 255));
+    let b = db
+        .lookup("2222222222222222222222222222222222222222")
+        .unwrap();
+    let q = b.quirks.unwrap();
+    assert!(q.shift_vy && !q.jump_vx);
+    assert_eq!(q.load_store, LoadStore::IncrementXPlusOne);
+    assert_eq!(b.platform, None);
+}
+
+#[test]
+fn test_merge_overrides() {
+    let mut db = Database::bundled();
+    let user = Database::from_json(&format!(
+        r#"{{ "programs": [ {{ "title": "My Pong", "roms": {{ "{}": {{ "tickrate": 20 }} }} }} ] }}"#,
+        PONG
+    ))
+    .unwrap();
+    let before = db.len();
+    db.merge(user);
+    assert_eq!(db.len(), before);
+    let pong = db.lookup(PONG).unwrap();
+    assert_eq!(pong.title, "My Pong");
+    assert_eq!(pong.tickrate, Some(20));
+}
+
+#[test]
+fn test_errors() {
+    let entry = |e: &str| {
+        Database::from_json(&format!(
+            r#"{{ "programs": [ {{ "title": "T", "roms": {{ "{}": {} }} }} ] }}"#,
+            PONG, e
+        ))
+    };
+    assert!(matches!(
+        entry(r#"{ "platform": "nes" }"#),
+        Err(DbError::Invalid(_))
+    ));
+    assert!(matches!(
+        entry(r#"{ "keys": { "up": 16 } }"#),
+        Err(DbError::Invalid(_))
+    ));
+    assert!(matches!(
+        entry(r#"{ "tickrate": 0 }"#),
+        Err(DbError::Invalid(_))
+    ));
+    assert!(matches!(
+        entry(r#"{ "tickrate": 4000000000 }"#),
+        Err(DbError::Invalid(_))
+    ));
+    assert!(matches!(entry(r#"{ "speed": 3 }"#), Err(DbError::Json(_))));
+    assert!(matches!(
+        Database::from_json(r#"{ "programs": [ { "title": "T", "roms": { "abc": {} } } ] }"#),
+        Err(DbError::Invalid(_))
+    ));
+    assert!(matches!(Database::from_json("[]"), Err(DbError::Json(_))));
+}
@@ -0,0 +1,73 @@
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::Sdl;
+
+use chip8emu::audio::{Beeper, Pattern, Tone};
+
+const SAMPLE_RATE: i32 = 44_100;
+
+// Generates the tone on SDL's audio thread while `on` is set.
+struct Synth {
+    tone: Tone,
+    pattern: Option<Pattern>,
+    on: bool,
+    phase: f32,
+    rate: f32,
+}
+
+impl AudioCallback for Synth {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        let frequency = match self.pattern {
+            Some(p) => p.frequency(),
+            None => self.tone.frequency,
+        };
+        for x in out.iter_mut() {
+            *x = match (self.on, self.pattern) {
+                (false, _) => 0.0,
+                (true, Some(p)) => p.sample(self.phase, self.tone.volume),
+                (true, None) => self.tone.sample(self.phase),
+            };
+            self.phase = (self.phase + frequency / self.rate).fract();
+        }
+    }
+}
+
+// Plays the buzzer through the default SDL audio output.
+pub struct SdlBeeper {
+    device: AudioDevice<Synth>,
+}
+
+impl SdlBeeper {
+    pub fn new(sdl_context: &Sdl, tone: Tone) -> Result<SdlBeeper, String> {
+        let audio = sdl_context.audio()?;
+        let spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+        let device = audio.open_playback(None, &spec, |spec| Synth {
+            tone,
+            pattern: None,
+            on: false,
+            phase: 0.0,
+            rate: spec.freq as f32,
+        })?;
+        device.resume();
+        Ok(SdlBeeper { device })
+    }
+}
+
+impl Beeper for SdlBeeper {
+    fn frame(&mut self, on: bool) {
+        self.device.lock().on = on;
+    }
+
+    fn set_tone(&mut self, tone: Tone) {
+        self.device.lock().tone = tone;
+    }
+
+    fn set_pattern(&mut self, pattern: Option<Pattern>) {
+        self.device.lock().pattern = pattern;
+    }
+}
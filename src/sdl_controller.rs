@@ -0,0 +1,101 @@
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::{GameControllerSubsystem, Sdl};
+
+// How far a stick or trigger has to move to press its key, out of 32767
+const AXIS_THRESHOLD: i16 = 16_000;
+
+// A button held down, or an axis pushed past the threshold one way
+#[derive(Copy, Clone)]
+enum Input {
+    Button(Button),
+    Axis(Axis, bool),
+}
+
+impl Input {
+    // Parses the names in chip8emu::keymap::CONTROLLER_BUTTONS and
+    // CONTROLLER_AXES
+    fn from_name(name: &str) -> Option<Input> {
+        if let Some(button) = Button::from_string(name) {
+            return Some(Input::Button(button));
+        }
+        match name.strip_suffix('+').or_else(|| name.strip_suffix('-')) {
+            Some(axis) => Axis::from_string(axis).map(|a| Input::Axis(a, name.ends_with('+'))),
+            None => Axis::from_string(name).map(|a| Input::Axis(a, true)),
+        }
+    }
+
+    fn is_held(self, c: &GameController) -> bool {
+        match self {
+            Input::Button(b) => c.button(b),
+            Input::Axis(a, true) => c.axis(a) > AXIS_THRESHOLD,
+            Input::Axis(a, false) => c.axis(a) < -AXIS_THRESHOLD,
+        }
+    }
+}
+
+// The game controllers plugged in, opened and closed as they come and go.
+pub struct Controllers {
+    subsystem: Option<GameControllerSubsystem>,
+    open: Vec<GameController>,
+    bindings: Vec<(Input, u8)>,
+}
+
+impl Controllers {
+    // `bindings` are controller input names and the keypad keys they press.
+    // Fails on a name SDL doesn't know; without SDL controller support the
+    // game is still played with the keyboard.
+    pub fn new<'a>(
+        sdl_context: &Sdl,
+        bindings: impl Iterator<Item = (&'a str, u8)>,
+    ) -> Result<Controllers, String> {
+        let bindings = bindings
+            .map(|(name, key)| match Input::from_name(name) {
+                Some(input) => Ok((input, key)),
+                None => Err(format!("unknown controller input {:?}", name)),
+            })
+            .collect::<Result<_, _>>()?;
+        let subsystem = sdl_context
+            .game_controller()
+            .map_err(|e| eprintln!("No game controllers: {}", e))
+            .ok();
+        Ok(Controllers {
+            subsystem,
+            open: Vec::new(),
+            bindings,
+        })
+    }
+
+    // Opens controllers as they are plugged in, including those present at
+    // startup, and drops them when unplugged.
+    pub fn handle(&mut self, event: &Event) {
+        let subsystem = match &self.subsystem {
+            Some(s) => s,
+            None => return,
+        };
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => match subsystem.open(which) {
+                Ok(c) => {
+                    println!("controller connected: {}", c.name());
+                    self.open.push(c);
+                }
+                Err(e) => eprintln!("couldn't open controller {}: {}", which, e),
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(i) = self.open.iter().position(|c| c.instance_id() == which) {
+                    println!("controller disconnected: {}", self.open[i].name());
+                    self.open.remove(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Whether any controller holds an input bound to keypad `key`.
+    pub fn is_held(&self, key: u8) -> bool {
+        self.bindings
+            .iter()
+            .filter(|&&(_, k)| k == key)
+            .any(|&(input, _)| self.open.iter().any(|c| input.is_held(c)))
+    }
+}
@@ -0,0 +1,231 @@
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cpu::{Cpu, Platform, HIRES_WIDTH, MAX_STACK_DEPTH, TIMER_HZ, XO_MEMORY_SIZE};
+
+// Save states start with this, followed by the format version and the
+// SHA-1 of the ROM they were taken from.
+const MAGIC: &[u8; 4] = b"C8ST";
+
+/// Current version of the save state format. States from other versions are
+/// rejected rather than half-restored.
+pub const STATE_VERSION: u16 = 3;
+
+/// Why a save state couldn't be restored.
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),
+    /// The data isn't a save state at all.
+    BadMagic,
+    /// The state was written by an incompatible version of the format.
+    UnsupportedVersion(u16),
+    /// The state belongs to another ROM.
+    RomMismatch,
+    /// The data ends early or has an out-of-range field.
+    Corrupt,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::Io(e) => write!(f, "{}", e),
+            StateError::BadMagic => write!(f, "not a chip8emu save state"),
+            StateError::UnsupportedVersion(v) => {
+                write!(f, "save state version {} isn't supported", v)
+            }
+            StateError::RomMismatch => write!(f, "save state belongs to a different ROM"),
+            StateError::Corrupt => write!(f, "save state is corrupt"),
+        }
+    }
+}
+
+impl error::Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(e: io::Error) -> StateError {
+        StateError::Io(e)
+    }
+}
+
+impl Cpu {
+    /// Writes the complete machine state to `path`.
+    ///
+    /// Configuration (quirks, clock rate, stack depth) and the keypad aren't
+    /// part of it.
+    pub fn save_state<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_state_bytes())
+    }
+
+    /// Restores a state written by [`save_state`](Cpu::save_state) for the
+    /// same ROM. On error the machine is left as it was.
+    pub fn load_state<P: AsRef<Path>>(&mut self, path: P) -> Result<(), StateError> {
+        let bytes = fs::read(path)?;
+        self.from_state_bytes(&bytes)
+    }
+
+    /// The machine state in the save state format.
+    pub fn to_state_bytes(&self) -> Vec<u8> {
+        let mut w = Vec::with_capacity(self.platform.memory_size() + 8192 + 128);
+        w.extend_from_slice(MAGIC);
+        w.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        w.extend_from_slice(&self.rom_hash);
+
+        w.push(match self.platform {
+            Platform::Chip8 => 0,
+            Platform::SuperChip => 1,
+            Platform::XoChip => 2,
+        });
+        w.extend_from_slice(&self.opcode.to_le_bytes());
+        let memory = &self.memory[..self.platform.memory_size()];
+        w.extend_from_slice(&(memory.len() as u32).to_le_bytes());
+        w.extend_from_slice(memory);
+        w.extend_from_slice(&self.v);
+        w.extend_from_slice(&self.i.to_le_bytes());
+        w.extend_from_slice(&self.pc.to_le_bytes());
+        w.extend_from_slice(&self.sp.to_le_bytes());
+        w.push(self.stack.len() as u8);
+        for s in self.stack.iter() {
+            w.extend_from_slice(&s.to_le_bytes());
+        }
+        w.push(self.delay_timer);
+        w.push(self.sound_timer);
+        w.push(self.wait_key as u8);
+        w.push(self.wait_reg as u8);
+        w.push(self.wait_held.unwrap_or(0xFF));
+        w.extend_from_slice(&self.cycle_acc.to_le_bytes());
+        w.extend_from_slice(&self.rng.state.to_le_bytes());
+        w.extend_from_slice(&self.rng.r9.to_le_bytes());
+        w.push(self.hires as u8);
+        w.push(self.halted as u8);
+        w.extend_from_slice(&self.rpl);
+        w.push(self.planes);
+        w.extend_from_slice(&self.audio);
+        w.push(self.pitch);
+        w.push(self.audio_loaded as u8);
+        for row in self.gfx.iter() {
+            w.extend_from_slice(row);
+        }
+        w
+    }
+
+    /// Restores the machine from [`to_state_bytes`](Cpu::to_state_bytes)
+    /// output. On error the machine is left as it was.
+    pub fn from_state_bytes(&mut self, bytes: &[u8]) -> Result<(), StateError> {
+        let mut r = Reader { bytes, pos: 0 };
+        if r.take(4)? != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let version = r.u16()?;
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        if r.take(20)? != self.rom_hash {
+            return Err(StateError::RomMismatch);
+        }
+
+        // Fill in a copy so a bad state can't leave the machine half-loaded
+        let mut cpu = *self;
+        cpu.platform = match r.u8()? {
+            0 => Platform::Chip8,
+            1 => Platform::SuperChip,
+            2 => Platform::XoChip,
+            _ => return Err(StateError::Corrupt),
+        };
+        cpu.opcode = r.u16()?;
+        let len = r.u32()? as usize;
+        if len != cpu.platform.memory_size() {
+            return Err(StateError::Corrupt);
+        }
+        cpu.memory = [0; XO_MEMORY_SIZE];
+        cpu.memory[..len].copy_from_slice(r.take(len)?);
+        cpu.v.copy_from_slice(r.take(16)?);
+        cpu.i = r.u16()?;
+        cpu.pc = r.u16()?;
+        cpu.sp = r.u16()?;
+        if r.u8()? as usize != cpu.stack.len() || cpu.sp as usize > MAX_STACK_DEPTH {
+            return Err(StateError::Corrupt);
+        }
+        for s in cpu.stack.iter_mut() {
+            *s = r.u16()?;
+        }
+        cpu.delay_timer = r.u8()?;
+        cpu.sound_timer = r.u8()?;
+        cpu.buzzed = false;
+        cpu.wait_key = r.u8()? != 0;
+        cpu.wait_reg = (r.u8()? & 0xF) as usize;
+        cpu.wait_held = match r.u8()? {
+            0xFF => None,
+            k => Some(k & 0xF),
+        };
+        cpu.cycle_acc = r.u32()?;
+        if cpu.cycle_acc >= TIMER_HZ {
+            return Err(StateError::Corrupt);
+        }
+        cpu.rng.state = r.u64()?;
+        if cpu.rng.state == 0 {
+            return Err(StateError::Corrupt);
+        }
+        cpu.rng.r9 = r.u16()?;
+        cpu.hires = r.u8()? != 0;
+        cpu.halted = r.u8()? != 0;
+        cpu.rpl.copy_from_slice(r.take(16)?);
+        cpu.planes = r.u8()? & 0x3;
+        cpu.audio.copy_from_slice(r.take(16)?);
+        cpu.pitch = r.u8()?;
+        cpu.audio_loaded = r.u8()? != 0;
+        for row in cpu.gfx.iter_mut() {
+            row.copy_from_slice(r.take(HIRES_WIDTH as usize)?);
+        }
+        if r.pos != bytes.len() {
+            return Err(StateError::Corrupt);
+        }
+
+        *self = cpu;
+        Ok(())
+    }
+}
+
+// Little-endian cursor over a save state.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], StateError> {
+        let end = self.pos + n;
+        if end > self.bytes.len() {
+            return Err(StateError::Corrupt);
+        }
+        let s = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(s)
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, StateError> {
+        let mut b = [0; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+}
+
+#[cfg(test)]
+#[path = "./state_tests.rs"]
+mod state_tests;
@@ -0,0 +1,113 @@
+use super::*;
+use crate::quirks::Quirks;
+
+// 6005 A20A D015 1206: draws a digit then loops forever
+const ROM: [u8; 8] = [0x60, 0x05, 0xA2, 0x0A, 0xD0, 0x15, 0x12, 0x06];
+
+#[test]
+fn test_round_trip() {
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    for _ in 0..4 {
+        cpu.emulate_cycle().unwrap();
+    }
+    cpu.delay_timer = 9;
+    cpu.press_key(3);
+    let bytes = cpu.to_state_bytes();
+
+    let mut other = Cpu::initialize(&ROM, Quirks::default());
+    other.from_state_bytes(&bytes).unwrap();
+    assert_eq!(other.to_state_bytes(), bytes);
+    assert_eq!(other.pc, cpu.pc);
+    assert_eq!(other.v, cpu.v);
+    assert_eq!(other.delay_timer, 9);
+    assert_eq!(&other.gfx[..], &cpu.gfx[..]);
+    // The keypad follows the player, not the state
+    assert!(!other.is_key_pressed(3));
+    // So do the random numbers
+    cpu.emulate_instruction(0xC1FF).unwrap();
+    other.emulate_instruction(0xC1FF).unwrap();
+    assert_eq!(other.v[1], cpu.v[1]);
+}
+
+#[test]
+fn test_round_trip_xochip() {
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    cpu.set_platform(Platform::XoChip);
+    cpu.memory[0xFFFF] = 0x42;
+    cpu.planes = 3;
+    let bytes = cpu.to_state_bytes();
+
+    let mut other = Cpu::initialize(&ROM, Quirks::default());
+    other.from_state_bytes(&bytes).unwrap();
+    assert_eq!(other.platform(), Platform::XoChip);
+    assert_eq!(other.memory[0xFFFF], 0x42);
+    assert_eq!(other.planes, 3);
+}
+
+#[test]
+fn test_save_load_file() {
+    let path = std::env::temp_dir().join("chip8emu_state_test.state");
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    cpu.emulate_cycle().unwrap();
+    cpu.save_state(&path).unwrap();
+
+    let mut other = Cpu::initialize(&ROM, Quirks::default());
+    other.load_state(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(other.v[0], 5);
+    assert_eq!(other.pc, 0x202);
+}
+
+#[test]
+fn test_rom_mismatch() {
+    let cpu = Cpu::initialize(&ROM, Quirks::default());
+    let bytes = cpu.to_state_bytes();
+    let mut other = Cpu::initialize(&[0x12, 0x00], Quirks::default());
+    match other.from_state_bytes(&bytes) {
+        Err(StateError::RomMismatch) => {}
+        r => panic!("expected a ROM mismatch, got {:?}", r),
+    }
+}
+
+#[test]
+fn test_bad_header() {
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    let mut bytes = cpu.to_state_bytes();
+
+    let mut wrong_version = bytes.clone();
+    wrong_version[4] = 99;
+    match cpu.from_state_bytes(&wrong_version) {
+        Err(StateError::UnsupportedVersion(99)) => {}
+        r => panic!("expected an unsupported version, got {:?}", r),
+    }
+
+    let mut behind = cpu;
+    behind.cycle_acc = TIMER_HZ;
+    assert!(matches!(
+        cpu.from_state_bytes(&behind.to_state_bytes()),
+        Err(StateError::Corrupt)
+    ));
+
+    bytes.truncate(bytes.len() - 1);
+    assert!(matches!(
+        cpu.from_state_bytes(&bytes),
+        Err(StateError::Corrupt)
+    ));
+    bytes[0] = b'X';
+    assert!(matches!(
+        cpu.from_state_bytes(&bytes),
+        Err(StateError::BadMagic)
+    ));
+}
+
+#[test]
+fn test_failed_load_keeps_state() {
+    let mut cpu = Cpu::initialize(&ROM, Quirks::default());
+    let mut bytes = cpu.to_state_bytes();
+    cpu.emulate_cycle().unwrap();
+    // Mess up the trailing screen data only
+    bytes.push(0);
+    assert!(cpu.from_state_bytes(&bytes).is_err());
+    assert_eq!(cpu.v[0], 5);
+    assert_eq!(cpu.pc, 0x202);
+}